@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+use std::collections::{vec_deque, VecDeque};
+use std::fmt;
+
+/// Longest line kept in one piece. Output without newlines, such as
+/// binary data, is split so a single line can't grow without bound.
+pub const MAX_LINE: usize = 64 * 1024;
+
+/// How many leading bytes [`Buffer::is_binary`] looks at
+const SNIFF: usize = 8 * 1024;
+
+/// Caps on the output kept in memory per stream, the oldest lines are
+/// dropped first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            lines: 100_000,
+            bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// How raw output is turned into text for display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Invalid sequences show up as `�`
+    #[default]
+    Utf8,
+    /// Every byte is the code point of the same value, so nothing is lost
+    Latin1,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 2] = [Encoding::Utf8, Encoding::Latin1];
+
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            Encoding::Latin1 => match std::str::from_utf8(bytes) {
+                Ok(text) if text.is_ascii() => Cow::Borrowed(text),
+                _ => Cow::Owned(bytes.iter().copied().map(char::from).collect()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Latin1 => write!(f, "Latin-1"),
+        }
+    }
+}
+
+pub trait Line {
+    /// Bytes counted against [`Limits::bytes`]
+    fn size(&self) -> usize;
+
+    /// What to display, may contain ANSI escapes
+    fn text(&self, encoding: Encoding) -> Cow<'_, str>;
+}
+
+/// Raw output, including the newline that ended it if any
+impl Line for Vec<u8> {
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn text(&self, encoding: Encoding) -> Cow<'_, str> {
+        let line = self.strip_suffix(b"\n").unwrap_or(self);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        encoding.decode(line)
+    }
+}
+
+/// Ring buffer of the most recent lines
+#[derive(Debug, Clone, Default)]
+pub struct Buffer<T = Vec<u8>> {
+    lines: VecDeque<T>,
+    bytes: usize,
+    limits: Limits,
+    /// Lines dropped to stay within the limits
+    truncated: usize,
+}
+
+impl<T: Line> Buffer<T> {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            bytes: 0,
+            limits,
+            truncated: 0,
+        }
+    }
+
+    pub fn push(&mut self, line: T) {
+        self.bytes += line.size();
+        self.lines.push_back(line);
+
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        // Always keep the latest line, even if it's over the limit by itself
+        while self.lines.len() > 1
+            && (self.lines.len() > self.limits.lines || self.bytes > self.limits.bytes)
+        {
+            if let Some(line) = self.lines.pop_front() {
+                self.bytes -= line.size();
+                self.truncated += 1;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.lines.get(index)
+    }
+
+    pub fn truncated(&self) -> usize {
+        self.truncated
+    }
+
+    /// Counts lines that were dropped before they reached this buffer
+    pub fn add_truncated(&mut self, lines: usize) {
+        self.truncated += lines;
+    }
+}
+
+impl<T> IntoIterator for Buffer<T> {
+    type Item = T;
+    type IntoIter = vec_deque::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.lines.into_iter()
+    }
+}
+
+impl Buffer<Vec<u8>> {
+    /// Appends raw output that isn't split into lines, e.g. PTY output
+    pub fn append(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            // Continue the last line until its newline or the length cap
+            let open = self
+                .lines
+                .back_mut()
+                .filter(|line| !line.ends_with(b"\n") && line.len() < MAX_LINE);
+            let room = open.as_ref().map_or(MAX_LINE, |line| MAX_LINE - line.len());
+
+            let end = bytes[..room.min(bytes.len())]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(room.min(bytes.len()), |index| index + 1);
+            let (part, rest) = bytes.split_at(end);
+
+            match open {
+                Some(line) => line.extend_from_slice(part),
+                None => self.lines.push_back(part.to_vec()),
+            }
+            self.bytes += part.len();
+
+            bytes = rest;
+        }
+
+        self.trim();
+    }
+
+    /// The kept output exactly as it was written, minus truncated lines
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.lines.iter().flatten().copied().collect()
+    }
+
+    /// Guesses whether the output is binary rather than text, from
+    /// the control characters at its start
+    pub fn is_binary(&self) -> bool {
+        let mut sniffed = 0;
+        let mut control = 0;
+
+        for byte in self.lines.iter().flatten().take(SNIFF) {
+            match byte {
+                0 => return true,
+                b'\t' | b'\n' | b'\r' | 0x08 | 0x0c | 0x1b => {}
+                0x01..=0x1f | 0x7f => control += 1,
+                _ => {}
+            }
+            sniffed += 1;
+        }
+
+        control * 10 > sniffed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(buffer: &Buffer) -> Vec<&[u8]> {
+        (0..buffer.len())
+            .filter_map(|index| buffer.get(index))
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    #[test]
+    fn drops_oldest_lines_over_the_line_limit() {
+        let mut buffer = Buffer::new(Limits {
+            lines: 2,
+            bytes: 1024,
+        });
+
+        for line in ["a\n", "b\n", "c\n"] {
+            buffer.push(line.into());
+        }
+
+        assert_eq!(lines(&buffer), [b"b\n", b"c\n"]);
+        assert_eq!(buffer.truncated(), 1);
+    }
+
+    #[test]
+    fn drops_oldest_lines_over_the_byte_limit() {
+        let mut buffer = Buffer::new(Limits {
+            lines: 100,
+            bytes: 6,
+        });
+
+        for line in ["aaa", "bbb", "ccc"] {
+            buffer.push(line.into());
+        }
+
+        assert_eq!(lines(&buffer), [b"bbb", b"ccc"]);
+        assert_eq!(buffer.truncated(), 1);
+
+        buffer.push("a line longer than the limit".into());
+
+        assert_eq!(lines(&buffer), [b"a line longer than the limit"]);
+        assert_eq!(buffer.truncated(), 3);
+    }
+
+    #[test]
+    fn append_joins_chunks_into_lines() {
+        let mut buffer = Buffer::new(Limits::default());
+
+        for chunk in ["one\ntw", "o", "\r\nthree\n", "", "four"] {
+            buffer.append(chunk.as_bytes());
+        }
+
+        assert_eq!(
+            lines(&buffer),
+            [&b"one\n"[..], b"two\r\n", b"three\n", b"four"]
+        );
+        assert_eq!(buffer.to_bytes(), b"one\ntwo\r\nthree\nfour");
+    }
+
+    #[test]
+    fn append_splits_long_lines() {
+        let mut buffer = Buffer::new(Limits::default());
+
+        buffer.append(&[b'x'; MAX_LINE - 1]);
+        buffer.append(b"yz\n");
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.get(0).map(Vec::len), Some(MAX_LINE));
+        assert_eq!(buffer.get(1).map(Vec::as_slice), Some(&b"z\n"[..]));
+    }
+
+    #[test]
+    fn decodes_lines_for_display() {
+        let line = b"caf\xe9\r\n".to_vec();
+
+        assert_eq!(line.text(Encoding::Utf8), "caf\u{fffd}");
+        assert_eq!(line.text(Encoding::Latin1), "café");
+    }
+
+    #[test]
+    fn detects_binary_output() {
+        let mut text = Buffer::new(Limits::default());
+        text.append("plain \x1b[1mtext\x1b[0m\twith caf\u{e9}\r\n".as_bytes());
+
+        let mut binary = Buffer::new(Limits::default());
+        binary.append(b"\x7fELF\x02\x01\x01\0\0\0");
+
+        assert!(!text.is_binary());
+        assert!(binary.is_binary());
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use nix::sys::signal;
+
+use crate::buffer::Buffer;
+
+#[derive(Debug)]
+pub struct Exited {
+    /// Decided by the last stage of a pipeline
+    pub outcome: Outcome,
+    /// How each stage of the pipeline ended, like bash's `PIPESTATUS`
+    pub stages: Vec<Termination>,
+    pub usage: Usage,
+    pub stdout: Buffer,
+    pub stderr: Buffer,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    /// Wall-clock time from spawn to exit
+    pub wall: Duration,
+    /// CPU time spent in user mode
+    pub user: Duration,
+    /// CPU time spent in the kernel
+    pub system: Duration,
+    /// Peak resident set size, in bytes
+    pub max_rss: u64,
+}
+
+impl Usage {
+    /// Usage of two pipeline stages that ran side by side
+    pub(crate) fn combine(self, other: Self) -> Self {
+        Self {
+            wall: self.wall.max(other.wall),
+            user: self.user + other.user,
+            system: self.system + other.system,
+            max_rss: self.max_rss.max(other.max_rss),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Exited(Termination),
+    /// Terminated for running longer than its timeout
+    TimedOut {
+        after: Duration,
+    },
+}
+
+/// How a process ended, decoded from its raw wait status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Code(i32),
+    Signaled {
+        signal: i32,
+        core_dumped: bool,
+    },
+    /// A raw status that isn't a termination, such as a continued process
+    Unknown(i32),
+}
+
+impl Termination {
+    /// Explanation for exit codes with a conventional meaning
+    pub fn hint(&self) -> Option<String> {
+        match *self {
+            Termination::Code(126) => Some("command found but not executable".into()),
+            Termination::Code(127) => Some("command not found".into()),
+            Termination::Code(130) => Some("interrupted (SIGINT)".into()),
+            // Shells report a child killed by signal N as 128 + N, but
+            // any program is free to exit with these codes
+            Termination::Code(code @ 129..=192) => {
+                Some(format!("likely killed by {}", signal_name(code - 128)))
+            }
+            Termination::Code(_) | Termination::Signaled { .. } | Termination::Unknown(_) => None,
+        }
+    }
+}
+
+impl From<ExitStatus> for Termination {
+    fn from(status: ExitStatus) -> Self {
+        if let Some(code) = status.code() {
+            Termination::Code(code)
+        } else if let Some(signal) = status.signal() {
+            Termination::Signaled {
+                signal,
+                core_dumped: status.core_dumped(),
+            }
+        } else {
+            Termination::Unknown(status.into_raw())
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Termination::Code(code) => write!(f, "exit {code}"),
+            Termination::Signaled {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "{}", signal_name(signal))?;
+
+                if core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+
+                Ok(())
+            }
+            Termination::Unknown(status) => write!(f, "wait status {status:#x}"),
+        }
+    }
+}
+
+fn signal_name(signal: i32) -> String {
+    match signal::Signal::try_from(signal) {
+        Ok(signal) => signal.as_str().into(),
+        Err(_) => format!("signal {signal}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_wait_statuses() {
+        let cases = [
+            (0x0300, Termination::Code(3)),
+            (
+                libc::SIGKILL,
+                Termination::Signaled {
+                    signal: libc::SIGKILL,
+                    core_dumped: false,
+                },
+            ),
+            (
+                libc::SIGSEGV | 0x80,
+                Termination::Signaled {
+                    signal: libc::SIGSEGV,
+                    core_dumped: true,
+                },
+            ),
+            // Only reported when asked for, the process hasn't ended
+            (libc::SIGSTOP << 8 | 0x7f, Termination::Unknown(0x137f)),
+            (0xffff, Termination::Unknown(0xffff)),
+        ];
+
+        for (raw, termination) in cases {
+            assert_eq!(Termination::from(ExitStatus::from_raw(raw)), termination);
+        }
+    }
+
+    #[test]
+    fn describes_terminations() {
+        let segfault = Termination::Signaled {
+            signal: libc::SIGSEGV,
+            core_dumped: true,
+        };
+
+        assert_eq!(Termination::Code(1).to_string(), "exit 1");
+        assert_eq!(segfault.to_string(), "SIGSEGV (core dumped)");
+        assert_eq!(
+            Termination::Unknown(0xffff).to_string(),
+            "wait status 0xffff"
+        );
+    }
+
+    #[test]
+    fn hints_at_conventional_exit_codes() {
+        let hint = |code| Termination::Code(code).hint();
+
+        assert_eq!(hint(0), None);
+        assert_eq!(hint(127).as_deref(), Some("command not found"));
+        assert_eq!(hint(130).as_deref(), Some("interrupted (SIGINT)"));
+        assert_eq!(hint(137).as_deref(), Some("likely killed by SIGKILL"));
+        assert_eq!(hint(200), None);
+    }
+}
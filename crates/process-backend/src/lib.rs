@@ -0,0 +1,244 @@
+//! Runs child processes & pipelines for an iced application. Jobs are
+//! queued through a [`Backend`] handle and everything that happens to them
+//! is reported as an [`Event`], either from the [`subscription`] or a plain
+//! [`stream`] outside of iced.
+//!
+//! Children are spawned & reaped on tokio, so the backend has to be polled
+//! from within a tokio runtime: in iced, enable its `tokio` feature so
+//! `executor::Default` is tokio's. Any other executor panics on the first
+//! spawn.
+//!
+//! Only Linux is supported: pipes are opened with `pipe2`, terminals are
+//! named with `ptsname_r` and watched files are followed with inotify.
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("process-backend only supports Linux");
+
+use std::any::TypeId;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use iced_native::futures::{future, stream, StreamExt};
+use iced_native::{subscription, Subscription};
+use nix::sys::signal;
+use tokio::io;
+use tokio::sync::mpsc::UnboundedSender;
+
+pub use self::exit::{Exited, Outcome, Termination, Usage};
+pub use self::options::{Builder, Size, SpawnOptions};
+use self::run::{step, State};
+use self::shell::Pipeline;
+
+pub mod buffer;
+pub mod shell;
+
+mod exit;
+mod options;
+mod run;
+mod spawn;
+mod watch;
+
+pub(crate) enum Request {
+    Queue(Job),
+    Cancel(JobId),
+    Unwatch(JobId),
+    Swap(JobId, JobId),
+    MaxParallel(usize),
+    Signal(JobId, Signal),
+    Terminate(JobId),
+    Write(JobId, String),
+    CloseStdin(JobId),
+    Resize(JobId, Size),
+    Close,
+}
+
+/// Identifies a job from the moment it's queued, across all of its
+/// reruns. Unlike a pid it's never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u32);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+    Stop,
+    Continue,
+}
+
+impl From<Signal> for signal::Signal {
+    fn from(signal: Signal) -> Self {
+        match signal {
+            Signal::Interrupt => signal::Signal::SIGINT,
+            Signal::Terminate => signal::Signal::SIGTERM,
+            Signal::Kill => signal::Signal::SIGKILL,
+            Signal::Stop => signal::Signal::SIGSTOP,
+            Signal::Continue => signal::Signal::SIGCONT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    /// How long [`Backend::terminate`] waits after `SIGTERM` before
+    /// escalating to `SIGKILL`
+    pub grace_period: Duration,
+    /// How many jobs may run at once, the rest wait in a queue
+    pub max_parallel: usize,
+    /// How long watched files have to stay unchanged before a rerun
+    pub debounce: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(5),
+            max_parallel: std::thread::available_parallelism().map_or(4, usize::from),
+            debounce: Duration::from_millis(200),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// What the backend reports back, in the order it happens
+#[derive(Debug)]
+pub enum Event {
+    /// The backend is ready, commands are sent through the handle
+    Setup(Backend),
+    /// A queued job was taken off the queue and spawned, with the pid of
+    /// the pipeline's last stage, or failed to start. The pid is only
+    /// informational, jobs are always referred to by their [`JobId`].
+    Spawned(JobId, io::Result<u32>),
+    /// A queued job was removed before it started
+    Cancelled(JobId),
+    /// A line read from a job's stdout or stderr, as raw bytes ending
+    /// with its newline, unless it was the last or over [`buffer::MAX_LINE`]
+    Output(JobId, Stream, Vec<u8>),
+    /// Raw output of a job running inside a pseudo-terminal
+    Terminal(JobId, Vec<u8>),
+    /// The job exited and all of its output was read, or waiting for it failed
+    ProcessExited(JobId, io::Result<Box<Exited>>),
+    /// Every job was killed after [`Backend::close`]
+    Closed,
+}
+
+#[derive(Debug)]
+pub struct Backend {
+    sender: UnboundedSender<Request>,
+    next_id: AtomicU32,
+}
+
+/// A command line waiting for a free slot
+#[derive(Clone)]
+pub(crate) struct Job {
+    pub(crate) id: JobId,
+    pub(crate) pipeline: Pipeline,
+    pub(crate) options: SpawnOptions,
+}
+
+impl Backend {
+    fn new(sender: UnboundedSender<Request>) -> Self {
+        Self {
+            sender,
+            next_id: AtomicU32::new(0),
+        }
+    }
+
+    pub fn close(&self) {
+        let _ = self.sender.send(Request::Close);
+    }
+
+    pub fn signal(&self, id: JobId, signal: Signal) {
+        let _ = self.sender.send(Request::Signal(id, signal));
+    }
+
+    /// Sends `SIGTERM`, following up with `SIGKILL` if the process is
+    /// still alive after the grace period
+    pub fn terminate(&self, id: JobId) {
+        let _ = self.sender.send(Request::Terminate(id));
+    }
+
+    pub fn write(&self, id: JobId, input: String) {
+        let _ = self.sender.send(Request::Write(id, input));
+    }
+
+    /// Closes the process' stdin once all pending writes are flushed,
+    /// signalling EOF. For terminal processes this sends `^D` instead.
+    pub fn close_stdin(&self, id: JobId) {
+        let _ = self.sender.send(Request::CloseStdin(id));
+    }
+
+    pub fn resize(&self, id: JobId, size: Size) {
+        let _ = self.sender.send(Request::Resize(id, size));
+    }
+
+    /// Queues the pipeline, returning the id its job is known by from
+    /// now on. Never blocks, the job is spawned by the backend once
+    /// fewer than the maximum number of jobs are running and the
+    /// result is reported by [`Event::Spawned`].
+    pub fn spawn(&self, pipeline: Pipeline, options: SpawnOptions) -> JobId {
+        let id = JobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+
+        let _ = self.sender.send(Request::Queue(Job {
+            id,
+            pipeline,
+            options,
+        }));
+
+        id
+    }
+
+    /// Removes a job from the queue, if it hasn't started yet, and
+    /// stops watching its files
+    pub fn cancel(&self, id: JobId) {
+        let _ = self.sender.send(Request::Cancel(id));
+    }
+
+    /// Stops rerunning the job when its watched files change, and removes
+    /// a run of it that's still queued
+    pub fn unwatch(&self, id: JobId) {
+        let _ = self.sender.send(Request::Unwatch(id));
+    }
+
+    /// Swaps the places of two queued jobs
+    pub fn swap(&self, a: JobId, b: JobId) {
+        let _ = self.sender.send(Request::Swap(a, b));
+    }
+
+    pub fn set_max_parallel(&self, max_parallel: usize) {
+        let _ = self.sender.send(Request::MaxParallel(max_parallel));
+    }
+}
+
+/// The backend as an iced subscription, starting with [`Event::Setup`].
+/// Needs a tokio executor, see the [crate docs](crate).
+pub fn subscription(settings: Settings) -> Subscription<Event> {
+    subscription::unfold(TypeId::of::<Backend>(), State::Idle, move |state| {
+        step(settings, state)
+    })
+}
+
+/// The backend as a plain stream, starting with [`Event::Setup`] and ending
+/// after [`Event::Closed`]. Has to be polled within a tokio runtime.
+pub fn stream(settings: Settings) -> impl iced_native::futures::Stream<Item = Event> {
+    stream::unfold(State::Idle, move |state| async move {
+        if let State::Closed = state {
+            return None;
+        }
+
+        Some(step(settings, state).await)
+    })
+    .filter_map(future::ready)
+}
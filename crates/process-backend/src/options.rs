@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::buffer::Limits;
+
+/// How to run a job, see [`SpawnOptions::builder`]
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Working directory, inherited from the app when `None`
+    pub cwd: Option<PathBuf>,
+    /// Start from an empty environment instead of inheriting the app's
+    pub clear_env: bool,
+    /// Variables to set, or to remove when the value is `None`
+    pub env: Vec<(String, Option<String>)>,
+    /// Run inside a pseudo-terminal of this size
+    pub pty: Option<Size>,
+    /// Terminate the process once it has run for this long
+    pub timeout: Option<Duration>,
+    /// Caps on the output kept for each stream
+    pub limits: Limits,
+    /// Only report output as it comes in, leaving the streams of
+    /// [`Exited`](crate::Exited) empty, for callers that keep it themselves
+    pub discard_output: bool,
+    /// Paths or globs relative to the working directory. The command
+    /// is rerun whenever a match changes, cancelling the current run.
+    pub watch: Vec<String>,
+}
+
+impl SpawnOptions {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+}
+
+/// Builds [`SpawnOptions`], starting from the defaults
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    options: SpawnOptions,
+}
+
+impl Builder {
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.options.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn clear_env(mut self, clear_env: bool) -> Self {
+        self.options.clear_env = clear_env;
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Removes an inherited variable
+    pub fn unset(mut self, key: impl Into<String>) -> Self {
+        self.options.env.push((key.into(), None));
+        self
+    }
+
+    pub fn pty(mut self, size: Size) -> Self {
+        self.options.pty = Some(size);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.options.limits = limits;
+        self
+    }
+
+    pub fn discard_output(mut self, discard_output: bool) -> Self {
+        self.options.discard_output = discard_output;
+        self
+    }
+
+    /// Adds a path or glob, like `src/**/*.rs`, to rerun the job on
+    pub fn watch(mut self, pattern: impl Into<String>) -> Self {
+        self.options.watch.push(pattern.into());
+        self
+    }
+
+    pub fn build(self) -> SpawnOptions {
+        self.options
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub rows: u16,
+    pub cols: u16,
+}
@@ -0,0 +1,696 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use iced_native::futures::stream::{BoxStream, SelectAll};
+use iced_native::futures::{stream, FutureExt, StreamExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+use tokio::{io, time};
+
+use crate::buffer::{Buffer, MAX_LINE};
+use crate::exit::{Exited, Outcome, Termination, Usage};
+use crate::spawn::{self, set_size, signal_group, Spawned};
+use crate::watch::{self, Watcher};
+use crate::{Backend, Event, Job, JobId, Request, Settings, Signal, SpawnOptions, Stream};
+
+pub(crate) enum Input {
+    Request(Request),
+    Output(JobId, Stream, Option<Vec<u8>>),
+    Terminal(JobId, Option<Vec<u8>>),
+    /// A stage of the pipeline was reaped
+    Process(JobId, usize, io::Result<(ExitStatus, Usage)>),
+    /// A file watched by the job changed
+    Changed(JobId),
+    Deadline,
+}
+
+pub(crate) enum State {
+    Idle,
+    Running {
+        receiver: UnboundedReceiver<Request>,
+        processes: Vec<Process>,
+        outputs: SelectAll<BoxStream<'static, Input>>,
+        queue: VecDeque<Job>,
+        max_parallel: usize,
+        watches: Vec<Watch>,
+    },
+    Closed,
+}
+
+/// Advances the backend by one event, or none for inputs it handles itself
+pub(crate) async fn step(settings: Settings, state: State) -> (Option<Event>, State) {
+    match state {
+        State::Idle => {
+            let (sender, receiver) = mpsc::unbounded_channel();
+
+            (
+                Some(Event::Setup(Backend::new(sender))),
+                State::Running {
+                    receiver,
+                    processes: vec![],
+                    outputs: SelectAll::new(),
+                    queue: VecDeque::new(),
+                    max_parallel: settings.max_parallel,
+                    watches: vec![],
+                },
+            )
+        }
+        State::Running {
+            mut receiver,
+            mut processes,
+            mut outputs,
+            mut queue,
+            mut max_parallel,
+            mut watches,
+        } => loop {
+            if processes.len() < max_parallel {
+                if let Some(Job {
+                    id,
+                    pipeline,
+                    options,
+                }) = queue.pop_front()
+                {
+                    let result = spawn::spawn(&pipeline, &options).map(|spawned| {
+                        let process = Process::new(id, spawned, &options, &mut outputs);
+                        // Every stage leads its own group, so the ids match
+                        let pid = *process.groups.last().expect("Pipeline has a stage");
+
+                        processes.push(process);
+                        pid
+                    });
+
+                    // Every rerun would fail the same way
+                    if result.is_err() {
+                        watches.retain(|watch| watch.job.id != id);
+                    }
+
+                    return (
+                        Some(Event::Spawned(id, result)),
+                        State::Running {
+                            receiver,
+                            processes,
+                            outputs,
+                            queue,
+                            max_parallel,
+                            watches,
+                        },
+                    );
+                }
+            }
+
+            let next = {
+                let deadline = processes
+                    .iter()
+                    .filter_map(Process::deadline)
+                    .chain(watches.iter().filter_map(|watch| watch.debounce_at))
+                    .min();
+
+                let deadline = match deadline {
+                    Some(deadline) => time::sleep_until(deadline)
+                        .map(|_| Input::Deadline)
+                        .into_stream()
+                        .boxed(),
+                    None => stream::empty().boxed(),
+                };
+
+                let receiver = receiver
+                    .recv()
+                    .into_stream()
+                    .filter_map(|request| async move { request.map(Input::Request) })
+                    .boxed();
+
+                stream::select(&mut outputs, stream::select(receiver, deadline))
+                    .next()
+                    .await
+            };
+
+            // Every handle was dropped and there's nothing left to wait for
+            let Some(input) = next else {
+                return (None, State::Closed);
+            };
+
+            let id = match input {
+                Input::Request(request) => match request {
+                    Request::Queue(job) => {
+                        if !job.options.watch.is_empty() {
+                            match Watch::new(&job, &mut outputs) {
+                                Ok(watch) => watches.push(watch),
+                                Err(err) => {
+                                    return (
+                                        Some(Event::Spawned(job.id, Err(err))),
+                                        State::Running {
+                                            receiver,
+                                            processes,
+                                            outputs,
+                                            queue,
+                                            max_parallel,
+                                            watches,
+                                        },
+                                    );
+                                }
+                            }
+                        }
+
+                        queue.push_back(job);
+                        continue;
+                    }
+                    // A queued rerun goes too, nothing follows the job once
+                    // it's unwatched
+                    Request::Unwatch(id) | Request::Cancel(id) => {
+                        watches.retain(|watch| watch.job.id != id);
+
+                        let Some(index) = queue.iter().position(|job| job.id == id) else {
+                            continue;
+                        };
+                        queue.remove(index);
+
+                        return (
+                            Some(Event::Cancelled(id)),
+                            State::Running {
+                                receiver,
+                                processes,
+                                outputs,
+                                queue,
+                                max_parallel,
+                                watches,
+                            },
+                        );
+                    }
+                    Request::Swap(a, b) => {
+                        let a = queue.iter().position(|job| job.id == a);
+                        let b = queue.iter().position(|job| job.id == b);
+
+                        if let (Some(a), Some(b)) = (a, b) {
+                            queue.swap(a, b);
+                        }
+                        continue;
+                    }
+                    Request::MaxParallel(max) => {
+                        max_parallel = max.max(1);
+                        continue;
+                    }
+                    Request::Signal(id, signal) => {
+                        if let Some(process) = processes.iter().find(|p| p.id == id) {
+                            process.signal(signal);
+                        }
+                        continue;
+                    }
+                    Request::Terminate(id) => {
+                        if let Some(process) = processes.iter_mut().find(|p| p.id == id) {
+                            process.terminate(settings.grace_period);
+                        }
+                        continue;
+                    }
+                    Request::Write(id, input) => {
+                        if let Some(stdin) = processes
+                            .iter()
+                            .find(|p| p.id == id)
+                            .and_then(|p| p.stdin.as_ref())
+                        {
+                            let _ = stdin.send(input);
+                        }
+                        continue;
+                    }
+                    Request::CloseStdin(id) => {
+                        if let Some(process) = processes.iter_mut().find(|p| p.id == id) {
+                            match (&process.pty, &process.stdin) {
+                                (Some(_), Some(stdin)) => {
+                                    let _ = stdin.send("\x04".into());
+                                }
+                                _ => process.stdin = None,
+                            }
+                        }
+                        continue;
+                    }
+                    Request::Resize(id, size) => {
+                        // Queued & rerun jobs start at the latest size
+                        let watched = watches.iter_mut().map(|watch| &mut watch.job);
+                        for job in queue.iter_mut().chain(watched) {
+                            if job.id == id {
+                                job.options.pty = job.options.pty.map(|_| size);
+                            }
+                        }
+                        if let Some(master) = processes
+                            .iter()
+                            .find(|p| p.id == id)
+                            .and_then(|p| p.pty.as_ref())
+                        {
+                            let _ = set_size(master, size);
+                        }
+                        continue;
+                    }
+                    Request::Close => {
+                        // Their waiters reap them in the background
+                        for process in &processes {
+                            process.signal(Signal::Kill);
+                        }
+
+                        return (Some(Event::Closed), State::Closed);
+                    }
+                },
+                Input::Output(id, stream, line) => {
+                    let Some(process) = processes.iter_mut().find(|p| p.id == id) else {
+                        continue;
+                    };
+
+                    match line {
+                        Some(line) => {
+                            if !process.discard_output {
+                                let buffer = match stream {
+                                    Stream::Stdout => &mut process.stdout,
+                                    Stream::Stderr => &mut process.stderr,
+                                };
+                                buffer.push(line.clone());
+                            }
+
+                            return (
+                                Some(Event::Output(id, stream, line)),
+                                State::Running {
+                                    receiver,
+                                    processes,
+                                    outputs,
+                                    queue,
+                                    max_parallel,
+                                    watches,
+                                },
+                            );
+                        }
+                        None => {
+                            process.open -= 1;
+                            id
+                        }
+                    }
+                }
+                Input::Terminal(id, chunk) => {
+                    let Some(process) = processes.iter_mut().find(|p| p.id == id) else {
+                        continue;
+                    };
+
+                    match chunk {
+                        Some(chunk) => {
+                            if !process.discard_output {
+                                process.stdout.append(&chunk);
+                            }
+
+                            return (
+                                Some(Event::Terminal(id, chunk)),
+                                State::Running {
+                                    receiver,
+                                    processes,
+                                    outputs,
+                                    queue,
+                                    max_parallel,
+                                    watches,
+                                },
+                            );
+                        }
+                        None => {
+                            process.open -= 1;
+                            id
+                        }
+                    }
+                }
+                Input::Process(id, stage, result) => {
+                    if let Some(process) = processes.iter_mut().find(|p| p.id == id) {
+                        process.statuses[stage] = Some(result);
+                    }
+                    id
+                }
+                Input::Changed(id) => {
+                    if let Some(watch) = watches.iter_mut().find(|w| w.job.id == id) {
+                        watch.debounce_at = Some(Instant::now() + settings.debounce);
+                    }
+                    continue;
+                }
+                Input::Deadline => {
+                    let now = Instant::now();
+
+                    for watch in watches
+                        .iter_mut()
+                        .filter(|watch| watch.debounce_at.is_some_and(|at| at <= now))
+                    {
+                        watch.debounce_at = None;
+
+                        let id = watch.job.id;
+                        if let Some(process) = processes.iter_mut().find(|p| p.id == id) {
+                            process.terminate(settings.grace_period);
+                            watch.rerun = true;
+                        } else if !watch.rerun && queue.iter().all(|job| job.id != id) {
+                            queue.push_back(watch.job.clone());
+                        }
+                    }
+
+                    for process in &mut processes {
+                        if process.kill_at.is_some_and(|kill_at| kill_at <= now) {
+                            process.signal(Signal::Kill);
+                            process.kill_at = None;
+                        }
+                        if process
+                            .timeout_at()
+                            .is_some_and(|timeout_at| timeout_at <= now)
+                        {
+                            process.timed_out = true;
+                            process.terminate(settings.grace_period);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            // Only report the exit once the status is known and all output has been read
+            if let Some(index) = processes
+                .iter()
+                .position(|process| process.id == id && process.is_finished())
+            {
+                let process = processes.remove(index);
+
+                if let Some(watch) = watches
+                    .iter_mut()
+                    .find(|watch| watch.job.id == id && watch.rerun)
+                {
+                    watch.rerun = false;
+                    queue.push_back(watch.job.clone());
+                }
+
+                return (
+                    Some(process.exited()),
+                    State::Running {
+                        receiver,
+                        processes,
+                        outputs,
+                        queue,
+                        max_parallel,
+                        watches,
+                    },
+                );
+            }
+        },
+        State::Closed => {
+            time::sleep(Duration::from_secs(1)).await;
+
+            (None, State::Closed)
+        }
+    }
+}
+
+pub(crate) struct Watch {
+    /// Queued again for every rerun
+    job: Job,
+    _watcher: Watcher,
+    /// When to rerun, pushed back by every change
+    debounce_at: Option<Instant>,
+    /// Rerun once the current run has exited
+    rerun: bool,
+}
+
+impl Watch {
+    fn new(job: &Job, outputs: &mut SelectAll<BoxStream<'static, Input>>) -> io::Result<Self> {
+        let cwd = match &job.options.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir()?,
+        };
+        let (watcher, changes) = watch::watch(&job.options.watch, &cwd)?;
+
+        let id = job.id;
+        outputs.push(changes.map(move |_| Input::Changed(id)).boxed());
+
+        Ok(Self {
+            job: job.clone(),
+            _watcher: watcher,
+            debounce_at: None,
+            rerun: false,
+        })
+    }
+}
+
+pub(crate) struct Process {
+    id: JobId,
+    /// Process group of each stage
+    groups: Vec<u32>,
+    stdin: Option<UnboundedSender<String>>,
+    /// Master side of the process' pseudo-terminal, if it has one
+    pty: Option<File>,
+    /// Status of each stage, once reaped
+    statuses: Vec<Option<io::Result<(ExitStatus, Usage)>>>,
+    stdout: Buffer,
+    stderr: Buffer,
+    discard_output: bool,
+    /// Number of output streams that haven't reached EOF yet
+    open: usize,
+    kill_at: Option<Instant>,
+    timeout: Option<Duration>,
+    started: Instant,
+    timed_out: bool,
+}
+
+impl Process {
+    fn new(
+        id: JobId,
+        spawned: Spawned,
+        options: &SpawnOptions,
+        outputs: &mut SelectAll<BoxStream<'static, Input>>,
+    ) -> Self {
+        let Spawned {
+            mut children,
+            readers,
+            pty,
+        } = spawned;
+
+        let mut open = 0;
+        let mut stdin = children[0].stdin.take().map(writer);
+
+        let pty = pty.map(|pty| {
+            outputs.push(chunks(id, tokio::fs::File::from_std(pty.reader)));
+            open += 1;
+
+            stdin = Some(writer(tokio::fs::File::from_std(pty.writer)));
+
+            pty.master
+        });
+
+        for child in &mut children {
+            if let Some(stdout) = child.stdout.take() {
+                outputs.push(lines(id, Stream::Stdout, stdout));
+                open += 1;
+            }
+            if let Some(stderr) = child.stderr.take() {
+                outputs.push(lines(id, Stream::Stderr, stderr));
+                open += 1;
+            }
+        }
+        for (stream, reader) in readers {
+            outputs.push(lines(id, stream, tokio::fs::File::from_std(reader)));
+            open += 1;
+        }
+
+        let started = Instant::now();
+        let groups = children.iter().filter_map(Child::id).collect();
+        let statuses = children.iter().map(|_| None).collect();
+
+        for (stage, child) in children.into_iter().enumerate() {
+            outputs.push(waiter(id, stage, child, started));
+        }
+
+        Self {
+            id,
+            groups,
+            stdin,
+            pty,
+            statuses,
+            stdout: Buffer::new(options.limits),
+            stderr: Buffer::new(options.limits),
+            discard_output: options.discard_output,
+            open,
+            kill_at: None,
+            timeout: options.timeout,
+            started,
+            timed_out: false,
+        }
+    }
+
+    /// Signals the whole process group, so descendants are included
+    /// even once the child itself has exited. The group id can't be
+    /// reused while any member is alive.
+    fn signal(&self, signal: Signal) {
+        for group in &self.groups {
+            let _ = signal_group(*group, signal);
+        }
+    }
+
+    fn terminate(&mut self, grace_period: Duration) {
+        self.signal(Signal::Terminate);
+        // A stopped process won't handle SIGTERM until it's resumed
+        self.signal(Signal::Continue);
+        self.kill_at = Some(Instant::now() + grace_period);
+    }
+
+    /// When the process times out, if it hasn't already. Descendants
+    /// still holding its output open count as the process running.
+    fn timeout_at(&self) -> Option<Instant> {
+        self.timeout
+            .filter(|_| !self.timed_out)
+            .map(|timeout| self.started + timeout)
+    }
+
+    /// The next time the backend needs to act on this process
+    fn deadline(&self) -> Option<Instant> {
+        self.kill_at.into_iter().chain(self.timeout_at()).min()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.statuses.iter().all(Option::is_some) && self.open == 0
+    }
+
+    fn exited(self) -> Event {
+        let Self {
+            id,
+            statuses,
+            stdout,
+            stderr,
+            timeout,
+            timed_out,
+            ..
+        } = self;
+
+        let exited = statuses
+            .into_iter()
+            .map(|status| status.expect("Process finished"))
+            .collect::<io::Result<Vec<_>>>()
+            .map(|statuses| {
+                let stages = statuses
+                    .iter()
+                    .map(|(status, _)| Termination::from(*status))
+                    .collect::<Vec<_>>();
+
+                let usage = statuses
+                    .iter()
+                    .map(|(_, usage)| *usage)
+                    .reduce(Usage::combine)
+                    .expect("Pipeline has a stage");
+
+                Box::new(Exited {
+                    outcome: match timeout {
+                        Some(after) if timed_out => Outcome::TimedOut { after },
+                        _ => Outcome::Exited(stages[stages.len() - 1]),
+                    },
+                    stages,
+                    usage,
+                    stdout,
+                    stderr,
+                })
+            });
+
+        Event::ProcessExited(id, exited)
+    }
+}
+
+/// Reaps the child with `wait4`, which unlike `Child::wait` also reports
+/// its resource usage. Holding on to `child` until then stops tokio from
+/// reaping it first.
+fn waiter(id: JobId, stage: usize, child: Child, started: Instant) -> BoxStream<'static, Input> {
+    let pid = child.id().expect("Child not yet reaped");
+
+    tokio::task::spawn_blocking(move || {
+        let result = wait(pid, started);
+        drop(child);
+        result
+    })
+    .map(move |result| {
+        Input::Process(
+            id,
+            stage,
+            result.unwrap_or_else(|err| Err(io::Error::other(err))),
+        )
+    })
+    .into_stream()
+    .boxed()
+}
+
+fn wait(pid: u32, started: Instant) -> io::Result<(ExitStatus, Usage)> {
+    let mut status = 0;
+    // Safety: `rusage` is plain old data
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    while unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut rusage) } == -1 {
+        let err = io::Error::last_os_error();
+
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let time = |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1_000);
+
+    let usage = Usage {
+        wall: started.elapsed(),
+        user: time(rusage.ru_utime),
+        system: time(rusage.ru_stime),
+        // In kilobytes
+        max_rss: rusage.ru_maxrss as u64 * 1024,
+    };
+
+    Ok((ExitStatus::from_raw(status), usage))
+}
+
+fn writer(mut stdin: impl AsyncWrite + Unpin + Send + 'static) -> UnboundedSender<String> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+    // Writes happen on their own task so a child that isn't reading
+    // can't stall the backend. Dropping `sender` drops `stdin` (EOF).
+    tokio::spawn(async move {
+        while let Some(input) = receiver.recv().await {
+            if stdin.write_all(input.as_bytes()).await.is_err() {
+                break;
+            }
+            let _ = stdin.flush().await;
+        }
+    });
+
+    sender
+}
+
+fn lines(
+    id: JobId,
+    stream: Stream,
+    io: impl AsyncRead + Unpin + Send + 'static,
+) -> BoxStream<'static, Input> {
+    // Yields every line followed by a final `None` once the stream is exhausted
+    stream::unfold(Some(BufReader::new(io)), move |reader| async move {
+        let mut reader = reader?;
+        let mut line = vec![];
+
+        // Bytes are kept as is, so output that isn't UTF-8 survives
+        match (&mut reader)
+            .take(MAX_LINE as u64)
+            .read_until(b'\n', &mut line)
+            .await
+        {
+            Ok(0) | Err(_) => Some((Input::Output(id, stream, None), None)),
+            Ok(_) => Some((Input::Output(id, stream, Some(line)), Some(reader))),
+        }
+    })
+    .boxed()
+}
+
+fn chunks(id: JobId, io: impl AsyncRead + Unpin + Send + 'static) -> BoxStream<'static, Input> {
+    // Reading the master fails with `EIO` once the child side is closed,
+    // which we treat as EOF
+    stream::unfold(Some(io), move |io| async move {
+        let mut io = io?;
+        let mut buffer = vec![0; 4096];
+
+        match io.read(&mut buffer).await {
+            Ok(0) | Err(_) => Some((Input::Terminal(id, None), None)),
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Input::Terminal(id, Some(buffer)), Some(io)))
+            }
+        }
+    })
+    .boxed()
+}
@@ -0,0 +1,573 @@
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::{env, fmt, vec};
+
+/// Commands connected by `|`, each reading the previous one's stdout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub commands: Vec<CommandLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLine {
+    pub env: Vec<(String, String)>,
+    pub program: String,
+    pub args: Vec<String>,
+    /// Applied in order, so `> file 2>&1` and `2>&1 > file` differ
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub fd: u32,
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Read(PathBuf),
+    Write(PathBuf),
+    Append(PathBuf),
+    /// Whatever the given descriptor currently refers to, as in `2>&1`
+    Fd(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Empty,
+    MissingProgram,
+    MissingTarget,
+    UnsupportedFd(u32),
+    UnterminatedQuote(char),
+    UnterminatedBrace,
+    /// A `${…}` with more than a variable name in it
+    BadSubstitution(String),
+    /// `;`, `&&`, `||` or `&`, which only a shell can run
+    ControlOperator(&'static str),
+    TrailingBackslash,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty command"),
+            Error::MissingProgram => write!(f, "no program after variable assignments"),
+            Error::MissingTarget => write!(f, "missing redirection target"),
+            Error::UnsupportedFd(fd) => {
+                write!(f, "can't redirect file descriptor {fd}, only 0, 1 and 2")
+            }
+            Error::UnterminatedQuote(quote) => write!(f, "unterminated {quote} quote"),
+            Error::UnterminatedBrace => write!(f, "unterminated ${{ expansion"),
+            Error::BadSubstitution(inner) => {
+                write!(f, "unsupported expansion ${{{inner}}}, try shell mode")
+            }
+            Error::ControlOperator(operator) => write!(f, "`{operator}` needs shell mode"),
+            Error::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Runs `input` verbatim through `$SHELL -c`, falling back to `/bin/sh`
+pub fn wrap(input: &str) -> Result<Pipeline, Error> {
+    if input.trim().is_empty() {
+        return Err(Error::Empty);
+    }
+
+    let program = env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "/bin/sh".into());
+
+    Ok(Pipeline {
+        commands: vec![CommandLine {
+            env: vec![],
+            program,
+            args: vec!["-c".into(), input.into()],
+            redirects: vec![],
+        }],
+    })
+}
+
+/// Splits `input` into words following POSIX shell quoting rules,
+/// expanding `~`, `$VAR` and `${VAR}` and collecting leading
+/// `VAR=value` assignments into the command's environment. Unquoted
+/// `|`, `<`, `>`, `>>` and `N>&M` build a pipeline with redirections,
+/// while `;`, `&&`, `||` and `&` are rejected as they need a shell.
+pub fn parse(input: &str) -> Result<Pipeline, Error> {
+    let mut tokens = tokenize(input)?.into_iter().peekable();
+    let mut commands = vec![command(&mut tokens)?];
+
+    while tokens.next().is_some() {
+        commands.push(command(&mut tokens)?);
+    }
+
+    Ok(Pipeline { commands })
+}
+
+/// Parses tokens up to the next `|`, leaving it unconsumed
+fn command(tokens: &mut Peekable<vec::IntoIter<Token>>) -> Result<CommandLine, Error> {
+    let mut env = vec![];
+    let mut words = vec![];
+    let mut redirects = vec![];
+
+    while let Some(token) = tokens.next_if(|token| !matches!(token, Token::Pipe)) {
+        match token {
+            Token::Word(Word {
+                value,
+                assignment: Some(index),
+            }) if words.is_empty() => {
+                let (key, value) = value.split_at(index);
+                env.push((key.to_string(), value[1..].to_string()));
+            }
+            Token::Word(word) => words.push(word.value),
+            Token::Redirect(fd, operator) => {
+                let mut path = || match tokens.next_if(|token| matches!(token, Token::Word(_))) {
+                    Some(Token::Word(word)) => Ok(PathBuf::from(word.value)),
+                    _ => Err(Error::MissingTarget),
+                };
+
+                let target = match operator {
+                    Operator::Read => Target::Read(path()?),
+                    Operator::Write => Target::Write(path()?),
+                    Operator::Append => Target::Append(path()?),
+                    Operator::Duplicate(target) if target > 2 => {
+                        return Err(Error::UnsupportedFd(target))
+                    }
+                    Operator::Duplicate(target) => Target::Fd(target),
+                };
+
+                if fd > 2 {
+                    return Err(Error::UnsupportedFd(fd));
+                }
+
+                redirects.push(Redirect { fd, target });
+            }
+            Token::Pipe => unreachable!(),
+        }
+    }
+
+    let mut words = words.into_iter();
+
+    match words.next() {
+        Some(program) => Ok(CommandLine {
+            env,
+            program,
+            args: words.collect(),
+            redirects,
+        }),
+        None if env.is_empty() && redirects.is_empty() => Err(Error::Empty),
+        None => Err(Error::MissingProgram),
+    }
+}
+
+enum Token {
+    Word(Word),
+    Pipe,
+    Redirect(u32, Operator),
+}
+
+enum Operator {
+    Read,
+    Write,
+    Append,
+    Duplicate(u32),
+}
+
+struct Word {
+    value: String,
+    /// Position of the `=` if this word is a valid `NAME=value` assignment
+    assignment: Option<usize>,
+}
+
+#[derive(Default)]
+struct Builder {
+    value: String,
+    started: bool,
+    quoted: bool,
+    assignment: Option<usize>,
+}
+
+impl Builder {
+    fn push(&mut self, c: char, quoted: bool) {
+        if quoted {
+            self.quoted = true;
+        } else if c == '=' && self.assignment.is_none() && !self.quoted && is_name(&self.value) {
+            self.assignment = Some(self.value.len());
+        }
+
+        self.started = true;
+        self.value.push(c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.started = true;
+        self.quoted = true;
+        self.value.push_str(s);
+    }
+
+    fn at_tilde_position(&self) -> bool {
+        !self.started || self.assignment.map(|index| index + 1) == Some(self.value.len())
+    }
+
+    fn finish(&mut self, tokens: &mut Vec<Token>) {
+        if self.started {
+            let builder = std::mem::take(self);
+            tokens.push(Token::Word(Word {
+                value: builder.value,
+                assignment: builder.assignment,
+            }));
+        }
+    }
+
+    /// Takes an unquoted number right before a redirection, as in `2>`
+    fn take_fd(&mut self) -> Option<u32> {
+        if self.quoted || self.value.is_empty() || !self.value.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let fd = self.value.parse().ok()?;
+        *self = Self::default();
+        Some(fd)
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut word = Builder::default();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => word.finish(&mut tokens),
+            '|' if chars.next_if_eq(&'|').is_some() => return Err(Error::ControlOperator("||")),
+            '|' => {
+                word.finish(&mut tokens);
+                tokens.push(Token::Pipe);
+            }
+            ';' => return Err(Error::ControlOperator(";")),
+            '&' if chars.next_if_eq(&'&').is_some() => return Err(Error::ControlOperator("&&")),
+            '&' => return Err(Error::ControlOperator("&")),
+            '<' | '>' => {
+                let fd = word.take_fd();
+                word.finish(&mut tokens);
+
+                let operator = match c {
+                    '<' => Operator::Read,
+                    _ if chars.next_if_eq(&'>').is_some() => Operator::Append,
+                    _ if chars.next_if_eq(&'&').is_some() => {
+                        let mut target = String::new();
+                        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                            target.push(c);
+                        }
+
+                        Operator::Duplicate(target.parse().map_err(|_| Error::MissingTarget)?)
+                    }
+                    _ => Operator::Write,
+                };
+
+                tokens.push(Token::Redirect(
+                    fd.unwrap_or(if c == '<' { 0 } else { 1 }),
+                    operator,
+                ));
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => word.push(c, true),
+                None => return Err(Error::TrailingBackslash),
+            },
+            '\'' => {
+                word.started = true;
+                word.quoted = true;
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c, true),
+                        None => return Err(Error::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                word.started = true;
+                word.quoted = true;
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c, true),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\', true);
+                                word.push(c, true);
+                            }
+                            None => return Err(Error::UnterminatedQuote('"')),
+                        },
+                        Some('$') => match variable(&mut chars)? {
+                            Some(value) => word.push_str(&value),
+                            None => word.push('$', true),
+                        },
+                        Some(c) => word.push(c, true),
+                        None => return Err(Error::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '$' => match variable(&mut chars)? {
+                Some(value) => word.push_str(&value),
+                None => word.push('$', false),
+            },
+            '~' if word.at_tilde_position()
+                && matches!(chars.peek(), None | Some('/' | ' ' | '\t' | '\n')) =>
+            {
+                match env::var("HOME") {
+                    Ok(home) => word.push_str(&home),
+                    Err(_) => word.push('~', false),
+                }
+            }
+            c => word.push(c, false),
+        }
+    }
+
+    word.finish(&mut tokens);
+
+    Ok(tokens)
+}
+
+/// Expands the variable following a `$`, returning `None` if no valid
+/// name follows so the `$` can be kept literally. Unset variables
+/// expand to an empty string, and `${…}` takes nothing but a name.
+fn variable(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Option<String>, Error> {
+    let mut name = String::new();
+
+    if chars.peek() == Some(&'{') {
+        chars.next();
+
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(c) => name.push(c),
+                None => return Err(Error::UnterminatedBrace),
+            }
+        }
+
+        if !is_name(&name) {
+            return Err(Error::BadSubstitution(name));
+        }
+    } else {
+        while let Some(&c) = chars.peek() {
+            if c == '_' || c.is_ascii_alphanumeric() && !(name.is_empty() && c.is_ascii_digit()) {
+                name.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if name.is_empty() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(env::var(name).unwrap_or_default()))
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<String> {
+        let mut pipeline = parse(input).unwrap();
+        let command = pipeline.commands.remove(0);
+
+        std::iter::once(command.program)
+            .chain(command.args)
+            .collect()
+    }
+
+    #[test]
+    fn splits_words_with_quotes_and_escapes() {
+        assert_eq!(
+            words("grep  \"foo bar\"   file"),
+            ["grep", "foo bar", "file"]
+        );
+        assert_eq!(
+            words(r#"echo 'a "b"' "c 'd'" e\ f"#),
+            ["echo", "a \"b\"", "c 'd'", "e f"]
+        );
+        assert_eq!(
+            words(r#"echo "\$x \n" '$x' """#),
+            ["echo", "$x \\n", "$x", ""]
+        );
+    }
+
+    #[test]
+    fn expands_variables_and_tilde() {
+        let home = env::var("HOME").unwrap();
+
+        assert_eq!(
+            words("echo ~/bin a~"),
+            ["echo", &format!("{home}/bin"), "a~"]
+        );
+        assert_eq!(
+            words("echo $HOME \"${HOME}x\" '$HOME' $"),
+            ["echo", &home, &format!("{home}x"), "$HOME", "$",]
+        );
+        assert_eq!(words("echo x${PROCESS_BACKEND_UNSET}y"), ["echo", "xy"]);
+    }
+
+    #[test]
+    fn joins_adjacent_quoted_parts() {
+        assert_eq!(
+            words(r#"echo a"b"'c'd "~" '~' \~ ''"#),
+            ["echo", "abcd", "~", "~", "~", ""]
+        );
+    }
+
+    #[test]
+    fn only_takes_unquoted_names_as_assignments() {
+        let home = env::var("HOME").unwrap();
+        let pipeline = parse("A=~/x B=$HOME 'C=1' D=2").unwrap();
+        let command = &pipeline.commands[0];
+
+        assert_eq!(
+            command.env,
+            [("A".into(), format!("{home}/x")), ("B".into(), home)]
+        );
+        assert_eq!(command.program, "C=1");
+        assert_eq!(command.args, ["D=2"]);
+        assert_eq!(words("1A=x =y"), ["1A=x", "=y"]);
+    }
+
+    #[test]
+    fn collects_leading_assignments() {
+        let pipeline = parse("A=1 B='x y' cmd C=2").unwrap();
+        let command = &pipeline.commands[0];
+
+        assert_eq!(
+            command.env,
+            [("A".into(), "1".into()), ("B".into(), "x y".into())]
+        );
+        assert_eq!(command.program, "cmd");
+        assert_eq!(command.args, ["C=2"]);
+    }
+
+    #[test]
+    fn builds_pipelines_with_redirections() {
+        let pipeline = parse("sort < in.txt 2>&1 | uniq -c >> out.txt 2>err").unwrap();
+        let redirects = pipeline
+            .commands
+            .iter()
+            .map(|command| command.redirects.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(pipeline.commands.len(), 2);
+        assert_eq!(pipeline.commands[1].args, ["-c"]);
+        assert_eq!(
+            redirects,
+            [
+                vec![
+                    Redirect {
+                        fd: 0,
+                        target: Target::Read("in.txt".into()),
+                    },
+                    Redirect {
+                        fd: 2,
+                        target: Target::Fd(1),
+                    },
+                ],
+                vec![
+                    Redirect {
+                        fd: 1,
+                        target: Target::Append("out.txt".into()),
+                    },
+                    Redirect {
+                        fd: 2,
+                        target: Target::Write("err".into()),
+                    },
+                ],
+            ]
+        );
+        assert_eq!(words("echo '|' \\> '2>&1'"), ["echo", "|", ">", "2>&1"]);
+        assert_eq!(words("echo ';' \\&\\& \"||\""), ["echo", ";", "&&", "||"]);
+    }
+
+    #[test]
+    fn splits_operators_without_spaces() {
+        let pipeline = parse("a|b>out 2>>err<in >&2").unwrap();
+
+        assert_eq!(pipeline.commands.len(), 2);
+        assert_eq!(pipeline.commands[0].program, "a");
+        assert_eq!(pipeline.commands[1].program, "b");
+        assert_eq!(
+            pipeline.commands[1].redirects,
+            [
+                Redirect {
+                    fd: 1,
+                    target: Target::Write("out".into()),
+                },
+                Redirect {
+                    fd: 2,
+                    target: Target::Append("err".into()),
+                },
+                Redirect {
+                    fd: 0,
+                    target: Target::Read("in".into()),
+                },
+                Redirect {
+                    fd: 1,
+                    target: Target::Fd(2),
+                },
+            ]
+        );
+        // Only a bare number is taken as the descriptor
+        assert_eq!(words("echo a2>&1")[1], "a2");
+    }
+
+    #[test]
+    fn reports_errors() {
+        let cases = [
+            ("", Error::Empty),
+            ("  ", Error::Empty),
+            ("ls |", Error::Empty),
+            ("A=1", Error::MissingProgram),
+            ("cat <", Error::MissingTarget),
+            ("cat > | wc", Error::MissingTarget),
+            ("cat 3> file", Error::UnsupportedFd(3)),
+            ("echo 'a", Error::UnterminatedQuote('\'')),
+            ("echo \"a", Error::UnterminatedQuote('"')),
+            ("echo ${HOME", Error::UnterminatedBrace),
+            (
+                "echo ${HOME:-/tmp}",
+                Error::BadSubstitution("HOME:-/tmp".into()),
+            ),
+            ("echo \"${#x}\"", Error::BadSubstitution("#x".into())),
+            ("echo ${}", Error::BadSubstitution(String::new())),
+            ("cd /tmp; ls", Error::ControlOperator(";")),
+            ("make && ./run", Error::ControlOperator("&&")),
+            ("test -f x || exit", Error::ControlOperator("||")),
+            ("sleep 1 &", Error::ControlOperator("&")),
+            ("echo a\\", Error::TrailingBackslash),
+        ];
+
+        for (input, error) in cases {
+            assert_eq!(parse(input), Err(error), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn wraps_input_in_a_shell() {
+        let pipeline = wrap("echo $HOME | wc").unwrap();
+
+        assert_eq!(pipeline.commands[0].args, ["-c", "echo $HOME | wc"]);
+        assert_eq!(wrap(" "), Err(Error::Empty));
+    }
+}
@@ -0,0 +1,382 @@
+use std::ffi::{CStr, OsStr};
+use std::fs::{File, OpenOptions};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use nix::pty;
+use nix::sys::signal;
+use nix::unistd::Pid;
+use tokio::io;
+use tokio::process::{Child, Command};
+
+use crate::shell::{self, CommandLine, Pipeline, Redirect, Target};
+use crate::{Signal, Size, SpawnOptions, Stream};
+
+/// Everything started for one pipeline
+pub(crate) struct Spawned {
+    pub(crate) children: Vec<Child>,
+    /// Pipes shared by several descriptors through `N>&M`, which the
+    /// backend reads itself
+    pub(crate) readers: Vec<(Stream, File)>,
+    pub(crate) pty: Option<Pty>,
+}
+
+pub(crate) struct Pty {
+    pub(crate) master: File,
+    pub(crate) reader: File,
+    pub(crate) writer: File,
+}
+
+impl From<Size> for pty::Winsize {
+    fn from(size: Size) -> Self {
+        pty::Winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+}
+
+/// What a child's stdin, stdout or stderr is connected to
+enum Io {
+    /// A pipe set up by tokio and read or written by the backend
+    Piped,
+    File(File),
+}
+
+/// Starts every command of the pipeline, stdout to stdin. Each stage
+/// leads its own session & process group, so its pid doubles as the
+/// group id and everything it starts can be signalled together.
+pub(crate) fn spawn(pipeline: &Pipeline, options: &SpawnOptions) -> io::Result<Spawned> {
+    validate(pipeline)?;
+
+    let mut spawned = Spawned {
+        children: vec![],
+        readers: vec![],
+        pty: None,
+    };
+
+    let slave = match options.pty {
+        Some(size) => {
+            let (master, slave) = open_pty(size)?;
+
+            spawned.pty = Some(Pty {
+                reader: master.try_clone()?,
+                writer: master.try_clone()?,
+                master,
+            });
+
+            Some(slave)
+        }
+        None => None,
+    };
+    let default = || match &slave {
+        Some(slave) => Ok(Io::File(slave.try_clone()?)),
+        None => Ok::<_, io::Error>(Io::Piped),
+    };
+
+    let mut stdin = default()?;
+    let last = pipeline.commands.len() - 1;
+
+    for (index, command_line) in pipeline.commands.iter().enumerate() {
+        let (stdout, next) = if index == last {
+            (default()?, None)
+        } else {
+            let (reader, writer) = pipe()?;
+            (Io::File(writer), Some(Io::File(reader)))
+        };
+
+        let mut stdio = [stdin, stdout, default()?];
+        redirect(
+            &mut stdio,
+            &command_line.redirects,
+            options,
+            &mut spawned.readers,
+        )?;
+
+        // A pager at the end of a pipeline reads keys from `/dev/tty`,
+        // so that stage gets the terminal
+        let terminal = slave.as_ref().filter(|_| index == last);
+        let mut command = command(command_line, options, stdio, terminal)?;
+
+        match command.spawn() {
+            Ok(child) => spawned.children.push(child),
+            Err(err) => {
+                // Dropped children are reaped by tokio
+                for child in &spawned.children {
+                    if let Some(id) = child.id() {
+                        let _ = signal_group(id, Signal::Kill);
+                    }
+                }
+                return Err(err);
+            }
+        }
+
+        stdin = next.unwrap_or(Io::Piped);
+    }
+
+    Ok(spawned)
+}
+
+/// Rejects what [`shell::parse`] never produces, but a pipeline built by
+/// hand could hold
+fn validate(pipeline: &Pipeline) -> io::Result<()> {
+    let invalid = |err: shell::Error| Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+
+    if pipeline.commands.is_empty() {
+        return invalid(shell::Error::Empty);
+    }
+
+    for Redirect { fd, target } in pipeline
+        .commands
+        .iter()
+        .flat_map(|command_line| &command_line.redirects)
+    {
+        match (fd, target) {
+            (fd @ 3.., _) | (_, Target::Fd(fd @ 3..)) => {
+                return invalid(shell::Error::UnsupportedFd(*fd))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies redirections left to right, as a shell would
+fn redirect(
+    stdio: &mut [Io; 3],
+    redirects: &[Redirect],
+    options: &SpawnOptions,
+    readers: &mut Vec<(Stream, File)>,
+) -> io::Result<()> {
+    let path = |path: &PathBuf| match &options.cwd {
+        Some(cwd) => cwd.join(path),
+        None => path.clone(),
+    };
+
+    for Redirect { fd, target } in redirects {
+        let io = match target {
+            Target::Read(file) => File::open(path(file))?,
+            Target::Write(file) => File::create(path(file))?,
+            Target::Append(file) => OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path(file))?,
+            Target::Fd(source) => {
+                let source = *source as usize;
+
+                // Share a pipe of our own rather than having tokio
+                // create a separate one for each descriptor
+                if let Io::Piped = stdio[source] {
+                    let stream = match source {
+                        1 => Stream::Stdout,
+                        2 => Stream::Stderr,
+                        _ => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "can't duplicate the piped stdin",
+                            ))
+                        }
+                    };
+
+                    let (reader, writer) = pipe()?;
+                    readers.push((stream, reader));
+                    stdio[source] = Io::File(writer);
+                }
+
+                match &stdio[source] {
+                    Io::File(file) => file.try_clone()?,
+                    Io::Piped => unreachable!(),
+                }
+            }
+        };
+
+        stdio[*fd as usize] = Io::File(io);
+    }
+
+    Ok(())
+}
+
+/// Both ends are close-on-exec from the start, so a child forked by
+/// another thread meanwhile can't hold on to the writer and keep the
+/// reader from seeing EOF
+pub(crate) fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Safety: `pipe2` hands us ownership of both descriptors
+    unsafe { Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))) }
+}
+
+fn command(
+    command_line: &CommandLine,
+    options: &SpawnOptions,
+    stdio: [Io; 3],
+    terminal: Option<&File>,
+) -> io::Result<Command> {
+    let mut command = Command::new(&command_line.program);
+    command.args(&command_line.args);
+
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(cwd);
+    }
+    if options.clear_env {
+        command.env_clear();
+    }
+    for (key, value) in &options.env {
+        match value {
+            Some(value) => command.env(key, value),
+            None => command.env_remove(key),
+        };
+    }
+    // Assignments on the command line itself take precedence
+    command.envs(command_line.env.iter().map(|(key, value)| (key, value)));
+
+    if options.pty.is_some() {
+        command.env("TERM", "xterm-256color");
+    }
+
+    let [stdin, stdout, stderr] = stdio.map(|io| match io {
+        Io::Piped => Stdio::piped(),
+        Io::File(file) => Stdio::from(file),
+    });
+    command.stdin(stdin);
+    command.stdout(stdout);
+    command.stderr(stderr);
+
+    match terminal {
+        // The pty becomes the controlling terminal of the new session,
+        // so job control & signals work
+        Some(terminal) => {
+            let terminal = terminal.as_raw_fd();
+
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setsid() == -1 || libc::ioctl(terminal, libc::TIOCSCTTY as _, 0) == -1
+                    {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        None => unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        },
+    }
+
+    Ok(command)
+}
+
+/// Like [`pipe`], neither end leaks into a child beyond its stdio
+fn open_pty(size: Size) -> io::Result<(File, File)> {
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Safety: `posix_openpt` hands us ownership of the descriptor
+    let master = unsafe { File::from_raw_fd(fd) };
+
+    if unsafe { libc::grantpt(fd) } == -1 || unsafe { libc::unlockpt(fd) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut name = [0; 64];
+    let result = unsafe { libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+
+    // Safety: `ptsname_r` wrote a NUL terminated path
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+
+    // Opened close-on-exec, like every file `std` opens
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(OsStr::from_bytes(name.to_bytes()))?;
+
+    set_size(&master, size)?;
+
+    Ok((master, slave))
+}
+
+pub(crate) fn set_size(master: &File, size: Size) -> io::Result<()> {
+    let winsize = pty::Winsize::from(size);
+
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn signal_group(pgid: u32, signal: Signal) -> nix::Result<()> {
+    signal::killpg(Pid::from_raw(pgid as i32), signal::Signal::from(signal))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::time::{self, Instant};
+
+    use super::*;
+    use crate::shell;
+
+    #[tokio::test]
+    async fn signals_reach_descendants() {
+        for signal in [Signal::Terminate, Signal::Kill] {
+            let pipeline =
+                shell::parse("sh -c 'sleep 100 & echo $!; sleep 100 & echo $!; wait'").unwrap();
+            let mut spawned = spawn(&pipeline, &SpawnOptions::default()).unwrap();
+
+            let mut child = spawned.children.remove(0);
+            let pgid = child.id().unwrap();
+
+            let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+            let mut descendants = vec![];
+            for _ in 0..2 {
+                let line = stdout.next_line().await.unwrap().unwrap();
+                descendants.push(line.parse::<i32>().unwrap());
+            }
+
+            signal_group(pgid, signal).unwrap();
+            child.wait().await.unwrap();
+
+            // Orphans are reaped by init, which may take a moment
+            let deadline = Instant::now() + Duration::from_secs(5);
+            for pid in descendants {
+                while is_alive(pid) {
+                    assert!(Instant::now() < deadline, "{pid} survived {signal:?}");
+                    time::sleep(Duration::from_millis(20)).await;
+                }
+            }
+        }
+    }
+
+    fn is_alive(pid: i32) -> bool {
+        // A zombie is dead, just not reaped yet
+        match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            Ok(stat) => !stat
+                .rsplit_once(") ")
+                .is_some_and(|(_, rest)| rest.starts_with('Z')),
+            Err(_) => signal::kill(Pid::from_raw(pid), None).is_ok(),
+        }
+    }
+}
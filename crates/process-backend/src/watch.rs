@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::{mem, ptr, thread};
+
+use iced_native::futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::spawn::pipe;
+
+const MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO;
+
+/// Watches files with inotify until dropped
+pub struct Watcher {
+    /// Closing the write end of this pipe wakes up the watching thread
+    _stop: File,
+}
+
+/// Starts watching the files matched by `patterns`, which are paths
+/// relative to `cwd` that may contain `*`, `?` and `**` wildcards.
+/// Yields the path of every file created, written, moved or deleted.
+pub fn watch(
+    patterns: &[String],
+    cwd: &Path,
+) -> io::Result<(Watcher, BoxStream<'static, PathBuf>)> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| Pattern::new(&cwd.join(pattern)))
+        .collect::<Vec<_>>();
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: `inotify_init1` hands us ownership of the descriptor
+    let inotify = unsafe { File::from_raw_fd(fd) };
+
+    let mut directories = HashMap::new();
+    for pattern in &patterns {
+        add_watches(
+            &inotify,
+            &pattern.directory,
+            pattern.recursive,
+            &mut directories,
+        )?;
+    }
+
+    let (stop, stop_writer) = pipe()?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    thread::spawn(move || run(inotify, stop, patterns, directories, sender));
+
+    let changes = stream::unfold(receiver, |mut receiver| async move {
+        let path = receiver.recv().await?;
+        Some((path, receiver))
+    })
+    .boxed();
+
+    Ok((Watcher { _stop: stop_writer }, changes))
+}
+
+struct Pattern {
+    components: Vec<String>,
+    /// Where watching starts
+    directory: PathBuf,
+    /// Whether changes in subdirectories can match
+    recursive: bool,
+    /// A plain directory, matching everything below it
+    prefix: bool,
+}
+
+impl Pattern {
+    fn new(path: &Path) -> Self {
+        let components = normalize(path);
+        let wildcard = components
+            .iter()
+            .position(|component| component.contains(['*', '?']));
+
+        // Leading part without wildcards
+        let root = components[..wildcard.unwrap_or(components.len())]
+            .iter()
+            .collect::<PathBuf>();
+
+        let (directory, recursive, prefix) = match wildcard {
+            Some(index) => {
+                let recursive = index < components.len() - 1
+                    || components[index..]
+                        .iter()
+                        .any(|component| component == "**");
+
+                (root, recursive, false)
+            }
+            None if root.is_dir() => (root, true, true),
+            None => (
+                root.parent()
+                    .map_or_else(|| root.clone(), Path::to_path_buf),
+                false,
+                false,
+            ),
+        };
+
+        Self {
+            components,
+            directory,
+            recursive,
+            prefix,
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let path = normalize(path);
+
+        if self.prefix {
+            path.starts_with(&self.components)
+        } else {
+            glob(&self.components, &path)
+        }
+    }
+}
+
+fn normalize(path: &Path) -> Vec<String> {
+    let mut components = vec![];
+
+    for component in path.components() {
+        match component {
+            Component::RootDir => components.push("/".into()),
+            Component::Normal(name) => components.push(name.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                if components.len() > 1 {
+                    components.pop();
+                }
+            }
+            Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+
+    components
+}
+
+fn glob(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| glob(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path.split_first().is_some_and(|(name, path)| {
+            let first = first.chars().collect::<Vec<_>>();
+            let name = name.chars().collect::<Vec<_>>();
+
+            wildcard(&first, &name) && glob(rest, path)
+        }),
+    }
+}
+
+fn wildcard(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && wildcard(rest, &name[1..]),
+    }
+}
+
+/// Watches `directory`, and every directory below it when recursive.
+/// Hidden directories such as `.git` are skipped.
+fn add_watches(
+    inotify: &File,
+    directory: &Path,
+    recursive: bool,
+    directories: &mut HashMap<i32, (PathBuf, bool)>,
+) -> io::Result<()> {
+    let path = CString::new(directory.as_os_str().as_bytes())?;
+
+    let wd = unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), MASK) };
+    if wd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    directories.insert(wd, (directory.to_path_buf(), recursive));
+
+    if recursive {
+        for entry in fs::read_dir(directory)?.flatten() {
+            let hidden = entry.file_name().as_bytes().starts_with(b".");
+
+            if !hidden && entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                // Directories can disappear while we walk them
+                let _ = add_watches(inotify, &entry.path(), true, directories);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run(
+    mut inotify: File,
+    stop: File,
+    patterns: Vec<Pattern>,
+    mut directories: HashMap<i32, (PathBuf, bool)>,
+    sender: UnboundedSender<PathBuf>,
+) {
+    let header = mem::size_of::<libc::inotify_event>();
+    let mut buffer = vec![0; 16 * 1024];
+
+    loop {
+        let mut fds = [inotify.as_raw_fd(), stop.as_raw_fd()].map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+
+        if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } == -1 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return;
+        }
+        // Hung up once the `Watcher` is dropped
+        if fds[1].revents != 0 {
+            return;
+        }
+
+        let read = match inotify.read(&mut buffer) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        };
+
+        let mut offset = 0;
+        while offset + header <= read {
+            // Safety: the kernel only writes whole events
+            let event: libc::inotify_event =
+                unsafe { ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+            let name = &buffer[offset + header..offset + header + event.len as usize];
+            offset += header + event.len as usize;
+
+            if event.mask & libc::IN_IGNORED != 0 {
+                directories.remove(&event.wd);
+                continue;
+            }
+
+            let path = match directories.get(&event.wd) {
+                Some((directory, recursive)) => {
+                    // The name is padded with NULs
+                    let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+                    let path = directory.join(OsStr::from_bytes(name));
+
+                    let created = event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0;
+                    if *recursive && created && event.mask & libc::IN_ISDIR != 0 {
+                        let _ = add_watches(&inotify, &path, true, &mut directories);
+                    }
+
+                    path
+                }
+                // Events were dropped, so anything may have changed
+                None if event.mask & libc::IN_Q_OVERFLOW != 0 => patterns[0].directory.clone(),
+                None => continue,
+            };
+
+            let matched = event.mask & libc::IN_Q_OVERFLOW != 0
+                || patterns.iter().any(|pattern| pattern.matches(&path));
+
+            if matched && sender.send(path).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Pattern::new(Path::new(pattern)).matches(Path::new(path))
+    }
+
+    #[test]
+    fn matches_wildcards_within_a_component() {
+        assert!(matches("/src/*.rs", "/src/main.rs"));
+        assert!(matches("/src/ma??.rs", "/src/main.rs"));
+        assert!(!matches("/src/*.rs", "/src/main.toml"));
+        assert!(!matches("/src/*.rs", "/src/bin/main.rs"));
+    }
+
+    #[test]
+    fn matches_any_depth_with_double_star() {
+        assert!(matches("/src/**/*.rs", "/src/main.rs"));
+        assert!(matches("/src/**/*.rs", "/src/a/b/main.rs"));
+        assert!(!matches("/src/**/*.rs", "/tests/main.rs"));
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert!(matches("/src/../tests/./*.rs", "/tests/backend.rs"));
+        assert!(matches("/src/*.rs", "/src/bin/../main.rs"));
+    }
+
+    #[test]
+    fn watches_from_the_leading_plain_directory() {
+        let watched = |pattern: &str| {
+            let pattern = Pattern::new(Path::new(pattern));
+            (pattern.directory, pattern.recursive)
+        };
+
+        assert_eq!(watched("/src/*.rs"), ("/src".into(), false));
+        assert_eq!(watched("/src/**/*.rs"), ("/src".into(), true));
+        assert_eq!(watched("/src/*/mod.rs"), ("/src".into(), true));
+        // A single file is watched through its parent
+        assert_eq!(watched("/no/such/file.rs"), ("/no/such".into(), false));
+    }
+
+    #[test]
+    fn matches_everything_below_a_plain_directory() {
+        let dir = std::env::temp_dir();
+        let pattern = dir.display().to_string();
+
+        assert!(matches(
+            &pattern,
+            &dir.join("a/b.txt").display().to_string()
+        ));
+        assert!(!matches(&pattern, "/elsewhere/b.txt"));
+        assert!(matches("/no/such/file.rs", "/no/such/file.rs"));
+        assert!(!matches("/no/such/file.rs", "/no/such/other.rs"));
+    }
+}
@@ -0,0 +1,474 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use iced_native::futures::stream::{BoxStream, StreamExt};
+use process_backend::buffer::{Buffer, Encoding, Line};
+use process_backend::shell::{Pipeline, Redirect, Target};
+use process_backend::{
+    shell, Backend, Event, Exited, JobId, Outcome, Settings, SpawnOptions, Stream, Termination,
+};
+use tokio::time::{self, Instant};
+
+/// Drives the backend stream and hands out its handle
+struct Harness {
+    backend: Backend,
+    events: BoxStream<'static, Event>,
+}
+
+impl Harness {
+    async fn new(settings: Settings) -> Self {
+        let mut events = process_backend::stream(settings).boxed();
+
+        match events.next().await {
+            Some(Event::Setup(backend)) => Self { backend, events },
+            other => panic!("Expected setup, got {other:?}"),
+        }
+    }
+
+    fn spawn(&self, command: &str, options: SpawnOptions) -> JobId {
+        self.backend.spawn(shell::parse(command).unwrap(), options)
+    }
+
+    async fn next(&mut self) -> Event {
+        time::timeout(Duration::from_secs(10), self.events.next())
+            .await
+            .expect("Timed out waiting for an event")
+            .expect("Backend stopped")
+    }
+
+    /// Skips events until `id` exits
+    async fn exited(&mut self, id: JobId) -> io::Result<Box<Exited>> {
+        loop {
+            if let Event::ProcessExited(exited, result) = self.next().await {
+                if exited == id {
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+fn settings() -> Settings {
+    Settings {
+        grace_period: Duration::from_millis(200),
+        max_parallel: 4,
+        ..Settings::default()
+    }
+}
+
+fn scripts() -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "scripts"]
+        .iter()
+        .collect()
+}
+
+fn script(name: &str) -> String {
+    scripts().join(name).display().to_string()
+}
+
+fn lines(buffer: &Buffer) -> Vec<String> {
+    (0..buffer.len())
+        .filter_map(|index| buffer.get(index))
+        .map(|line| line.text(Encoding::Utf8).into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn reports_output_and_exit_code() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(
+        &format!("{} 3", script("greet.sh")),
+        SpawnOptions::default(),
+    );
+
+    let mut output = vec![];
+    let exited = loop {
+        match harness.next().await {
+            Event::Output(job, stream, line) if job == id => output.push((stream, line)),
+            Event::ProcessExited(job, result) if job == id => break result.unwrap(),
+            _ => {}
+        }
+    };
+
+    output.sort_by_key(|(stream, _)| *stream == Stream::Stderr);
+    assert_eq!(
+        output,
+        [
+            (Stream::Stdout, b"hello from stdout\n".to_vec()),
+            (Stream::Stderr, b"hello from stderr\n".to_vec()),
+        ]
+    );
+    assert!(matches!(
+        exited.outcome,
+        Outcome::Exited(Termination::Code(3))
+    ));
+    assert_eq!(lines(&exited.stdout), ["hello from stdout"]);
+    assert_eq!(lines(&exited.stderr), ["hello from stderr"]);
+}
+
+#[tokio::test]
+async fn pipes_stages_together() {
+    let mut harness = Harness::new(settings()).await;
+    let command = format!("{} 4 2>&1 | tr a-z A-Z", script("greet.sh"));
+    let id = harness.spawn(&command, SpawnOptions::default());
+
+    let exited = harness.exited(id).await.unwrap();
+
+    assert!(matches!(
+        exited.outcome,
+        Outcome::Exited(Termination::Code(0))
+    ));
+    assert_eq!(exited.stages, [Termination::Code(4), Termination::Code(0)]);
+    assert_eq!(
+        lines(&exited.stdout),
+        ["HELLO FROM STDOUT", "HELLO FROM STDERR"]
+    );
+    assert!(exited.stderr.is_empty());
+}
+
+#[tokio::test]
+async fn redirects_relative_to_the_working_directory() {
+    let dir = std::env::temp_dir().join(format!("process-backend-redirect-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("in.txt"), "a\nb\n").unwrap();
+    fs::write(dir.join("err.txt"), "before\n").unwrap();
+
+    let mut harness = Harness::new(settings()).await;
+    let options = SpawnOptions::builder().cwd(&dir).build();
+    let command = format!(
+        "{} < in.txt > out.txt | {} 2>> err.txt",
+        script("echo.sh"),
+        script("greet.sh")
+    );
+    let id = harness.spawn(&command, options);
+
+    let exited = harness.exited(id).await.unwrap();
+
+    assert_eq!(lines(&exited.stdout), ["hello from stdout"]);
+    assert!(exited.stderr.is_empty());
+    assert_eq!(
+        fs::read_to_string(dir.join("out.txt")).unwrap(),
+        "got a\ngot b\neof\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("err.txt")).unwrap(),
+        "before\nhello from stderr\n"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn applies_spawn_options() {
+    let mut harness = Harness::new(settings()).await;
+    let options = SpawnOptions::builder()
+        .cwd(scripts())
+        .env("GREETING", "hi")
+        .unset("HOME")
+        .build();
+    let id = harness.spawn(&script("env.sh"), options);
+
+    let exited = harness.exited(id).await.unwrap();
+    let cwd = scripts().canonicalize().unwrap();
+
+    assert_eq!(
+        lines(&exited.stdout),
+        [cwd.to_str().unwrap(), "GREETING=hi HOME=unset"]
+    );
+}
+
+#[tokio::test]
+async fn writes_to_stdin() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(&script("echo.sh"), SpawnOptions::default());
+
+    harness.backend.write(id, "ping\n".into());
+
+    loop {
+        if let Event::Output(job, Stream::Stdout, line) = harness.next().await {
+            assert_eq!((job, line.as_slice()), (id, &b"got ping\n"[..]));
+            break;
+        }
+    }
+
+    harness.backend.close_stdin(id);
+    let exited = harness.exited(id).await.unwrap();
+
+    assert_eq!(lines(&exited.stdout), ["got ping", "eof"]);
+}
+
+#[tokio::test]
+async fn escalates_to_kill_after_the_grace_period() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(&script("stubborn.sh"), SpawnOptions::default());
+
+    loop {
+        if let Event::Output(_, Stream::Stdout, line) = harness.next().await {
+            assert_eq!(line, b"ready\n");
+            break;
+        }
+    }
+
+    let start = Instant::now();
+    harness.backend.terminate(id);
+    let exited = harness.exited(id).await.unwrap();
+
+    assert!(start.elapsed() >= settings().grace_period);
+    assert!(matches!(
+        exited.outcome,
+        Outcome::Exited(Termination::Signaled {
+            signal: libc::SIGKILL,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn times_out() {
+    let mut harness = Harness::new(settings()).await;
+    let timeout = Duration::from_millis(100);
+    let options = SpawnOptions::builder().timeout(timeout).build();
+    let id = harness.spawn(&script("stubborn.sh"), options);
+
+    let exited = harness.exited(id).await.unwrap();
+
+    assert!(matches!(exited.outcome, Outcome::TimedOut { after } if after == timeout));
+}
+
+#[tokio::test]
+async fn queues_jobs_beyond_the_limit() {
+    let mut harness = Harness::new(Settings {
+        max_parallel: 1,
+        ..settings()
+    })
+    .await;
+
+    // Blocks on stdin, holding the only slot until it's closed
+    let first = harness.spawn(&script("echo.sh"), SpawnOptions::default());
+    let second = harness.spawn(&script("greet.sh"), SpawnOptions::default());
+    let third = harness.spawn(&script("greet.sh"), SpawnOptions::default());
+
+    harness.backend.cancel(third);
+
+    let mut events = vec![];
+    while events.len() < 5 {
+        match harness.next().await {
+            Event::Spawned(id, _) => events.push(format!("spawned {id}")),
+            Event::Cancelled(id) => {
+                events.push(format!("cancelled {id}"));
+                harness.backend.close_stdin(first);
+            }
+            Event::ProcessExited(id, _) => events.push(format!("exited {id}")),
+            _ => {}
+        }
+    }
+
+    assert_eq!(
+        events,
+        [
+            format!("spawned {first}"),
+            format!("cancelled {third}"),
+            format!("exited {first}"),
+            format!("spawned {second}"),
+            format!("exited {second}"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn reports_spawn_failures() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(&script("missing.sh"), SpawnOptions::default());
+
+    let error = loop {
+        if let Event::Spawned(spawned, result) = harness.next().await {
+            assert_eq!(spawned, id);
+            break result.unwrap_err();
+        }
+    };
+
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+}
+
+#[tokio::test]
+async fn queues_a_burst_without_blocking() {
+    let mut harness = Harness::new(settings()).await;
+
+    // Nothing reads requests until the stream is polled again
+    let ids = (0..100)
+        .map(|_| harness.spawn(&script("greet.sh"), SpawnOptions::default()))
+        .collect::<Vec<_>>();
+
+    let mut pids = vec![];
+    let mut exited = vec![];
+    while exited.len() < ids.len() {
+        match harness.next().await {
+            Event::Spawned(_, result) => pids.push(result.unwrap()),
+            Event::ProcessExited(id, result) => {
+                assert!(result.is_ok());
+                exited.push(id);
+            }
+            _ => {}
+        }
+    }
+
+    exited.sort_unstable();
+    assert_eq!(exited, ids);
+    assert_eq!(pids.len(), ids.len());
+}
+
+#[tokio::test]
+async fn only_reports_discarded_output() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(
+        &script("greet.sh"),
+        SpawnOptions::builder().discard_output(true).build(),
+    );
+
+    let mut output = 0;
+    let exited = loop {
+        match harness.next().await {
+            Event::Output(job, ..) if job == id => output += 1,
+            Event::ProcessExited(job, result) if job == id => break result.unwrap(),
+            _ => {}
+        }
+    };
+
+    assert_eq!(output, 2);
+    assert!(exited.stdout.is_empty());
+    assert!(exited.stderr.is_empty());
+}
+
+#[tokio::test]
+async fn keeps_output_that_is_not_text() {
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(&script("binary.sh"), SpawnOptions::default());
+
+    let exited = harness.exited(id).await.unwrap();
+
+    assert_eq!(exited.stdout.to_bytes(), b"caf\xe9\n\0\x01\x02\xff");
+    assert_eq!(lines(&exited.stdout)[0], "caf\u{fffd}");
+    assert!(exited.stdout.is_binary());
+}
+
+#[tokio::test]
+async fn reruns_when_a_watched_file_changes() {
+    let dir = std::env::temp_dir().join(format!("process-backend-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(
+        &script("greet.sh"),
+        SpawnOptions::builder().cwd(&dir).watch("*.txt").build(),
+    );
+    assert!(harness.exited(id).await.is_ok());
+
+    fs::write(dir.join("changed.txt"), "").unwrap();
+    let rerun = loop {
+        if let Event::Spawned(spawned, result) = harness.next().await {
+            assert_eq!(spawned, id);
+            break result;
+        }
+    };
+    assert!(rerun.is_ok());
+    assert!(harness.exited(id).await.is_ok());
+
+    harness.backend.unwatch(id);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn unwatching_drops_a_queued_rerun() {
+    let dir = std::env::temp_dir().join(format!("process-backend-unwatch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut harness = Harness::new(Settings {
+        max_parallel: 1,
+        ..settings()
+    })
+    .await;
+    let id = harness.spawn(
+        &script("greet.sh"),
+        SpawnOptions::builder().cwd(&dir).watch("*.txt").build(),
+    );
+    assert!(harness.exited(id).await.is_ok());
+
+    // Holds the only slot, so the rerun waits in the queue
+    let blocker = harness.spawn("sleep 2", SpawnOptions::default());
+    fs::write(dir.join("changed.txt"), "").unwrap();
+    let waited = time::timeout(Duration::from_millis(500), harness.exited(blocker)).await;
+    assert!(waited.is_err());
+
+    harness.backend.unwatch(id);
+    match harness.next().await {
+        Event::Cancelled(cancelled) => assert_eq!(cancelled, id),
+        other => panic!("Expected the rerun to be cancelled, got {other:?}"),
+    }
+
+    assert!(harness.exited(blocker).await.is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn stops_watching_when_a_rerun_fails_to_spawn() {
+    let dir = std::env::temp_dir().join(format!("process-backend-missing-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let command = dir.join("greet.sh");
+    fs::copy(script("greet.sh"), &command).unwrap();
+
+    let mut harness = Harness::new(settings()).await;
+    let id = harness.spawn(
+        &command.display().to_string(),
+        SpawnOptions::builder().cwd(&dir).watch("*").build(),
+    );
+    assert!(harness.exited(id).await.is_ok());
+
+    // Removing the command is a change too, its rerun can't spawn
+    fs::remove_file(&command).unwrap();
+    let rerun = loop {
+        if let Event::Spawned(spawned, result) = harness.next().await {
+            assert_eq!(spawned, id);
+            break result;
+        }
+    };
+    assert!(rerun.is_err());
+
+    fs::write(dir.join("changed.txt"), "").unwrap();
+    let next = time::timeout(Duration::from_secs(1), harness.next()).await;
+    assert!(next.is_err(), "Expected no more reruns, got {next:?}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn rejects_pipelines_the_parser_wouldnt_build() {
+    let mut harness = Harness::new(settings()).await;
+
+    let mut redirected = shell::parse(&script("greet.sh")).unwrap();
+    redirected.commands[0].redirects.push(Redirect {
+        fd: 1,
+        target: Target::Fd(5),
+    });
+
+    let empty = harness
+        .backend
+        .spawn(Pipeline { commands: vec![] }, SpawnOptions::default());
+    let redirected = harness.backend.spawn(redirected, SpawnOptions::default());
+
+    for id in [empty, redirected] {
+        let error = loop {
+            if let Event::Spawned(spawned, result) = harness.next().await {
+                assert_eq!(spawned, id);
+                break result.unwrap_err();
+            }
+        };
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    // The backend is still running
+    let id = harness.spawn(&script("greet.sh"), SpawnOptions::default());
+    assert!(harness.exited(id).await.is_ok());
+}
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Writes Latin-1 text, then bytes that aren't text at all
+printf 'caf\351\n'
+printf '\000\001\002\377'
@@ -0,0 +1,6 @@
+#!/bin/sh
+# Echoes stdin back line by line until EOF
+while read -r line; do
+    echo "got $line"
+done
+echo "eof"
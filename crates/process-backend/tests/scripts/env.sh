@@ -0,0 +1,4 @@
+#!/bin/sh
+# Prints its working directory and a few variables
+pwd
+echo "GREETING=${GREETING-unset} HOME=${HOME-unset}"
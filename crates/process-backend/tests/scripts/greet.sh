@@ -0,0 +1,5 @@
+#!/bin/sh
+# Writes a line to each stream, then exits with the code given as $1
+echo "hello from stdout"
+echo "hello from stderr" >&2
+exit "${1:-0}"
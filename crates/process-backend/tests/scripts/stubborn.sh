@@ -0,0 +1,7 @@
+#!/bin/sh
+# Ignores SIGTERM, so only SIGKILL ends it
+trap '' TERM
+echo "ready"
+while :; do
+    sleep 1
+done
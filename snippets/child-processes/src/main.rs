@@ -1,14 +1,26 @@
-use iced::widget::{column, container};
+use std::path::PathBuf;
+
+use iced::widget::scrollable::RelativeOffset;
+use iced::widget::{column, container, row, scrollable, text, text_input};
 use iced::{
-    executor, subscription, window, Application, Command, Element, Event, Length, Settings,
-    Subscription, Theme,
+    clipboard, event, executor, keyboard, subscription, window, Alignment, Application, Command,
+    Element, Event, Length, Settings, Subscription, Theme,
 };
+use iced_native::widget;
+use process_backend::{self as backend, Backend, JobId};
 
-use self::backend::Backend;
+use self::history::History;
+use self::input::Input;
 use self::process::Process;
 
+const WINDOW_SIZE: (u32, u32) = (1024, 768);
+
 fn main() {
     App::run(Settings {
+        window: window::Settings {
+            size: WINDOW_SIZE,
+            ..window::Settings::default()
+        },
         exit_on_close_request: false,
         ..Default::default()
     })
@@ -17,14 +29,33 @@ fn main() {
 
 #[derive(Debug)]
 enum Message {
-    Event(Event),
-    Process(process::Message),
-    Backend(backend::Message),
+    Event(Event, event::Status),
+    /// A shortcut key, with the widget that had focus when it was pressed
+    Shortcut(
+        keyboard::KeyCode,
+        keyboard::Modifiers,
+        event::Status,
+        Option<widget::Id>,
+    ),
+    MaxParallel(String),
+    Input(input::Message),
+    Process(usize, process::Message),
+    /// Output was written to a file, for the process with that history index,
+    /// wherever it's listed by now
+    Saved(usize, Result<PathBuf, String>),
+    Backend(backend::Event),
 }
 
 enum App {
     Idle,
-    Running { backend: Backend, process: Process },
+    Running {
+        backend: Backend,
+        input: Input,
+        processes: Vec<Process>,
+        selected: Option<usize>,
+        terminal_size: backend::Size,
+        max_parallel: String,
+    },
 }
 
 impl Application for App {
@@ -43,45 +74,306 @@ impl Application for App {
 
     fn subscription(&self) -> Subscription<Message> {
         Subscription::batch(vec![
-            subscription::events().map(Message::Event),
-            backend::run().map(Message::Backend),
+            // Shortcuts should work even while a text input has focus
+            subscription::events_with(|event, status| match status {
+                event::Status::Ignored => Some(Message::Event(event, status)),
+                event::Status::Captured => {
+                    matches!(event, Event::Keyboard(_)).then_some(Message::Event(event, status))
+                }
+            }),
+            backend::subscription(backend::Settings::default()).map(Message::Backend),
         ])
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::Event(Event::Window(window::Event::CloseRequested)) => {
-                if let Self::Running { backend, .. } = self {
+            Message::Event(Event::Window(window::Event::CloseRequested), _) => {
+                if let Self::Running {
+                    backend,
+                    input,
+                    processes,
+                    ..
+                } = self
+                {
+                    for process in processes.iter().filter(|process| process.is_active()) {
+                        let status = if process.is_queued() {
+                            "cancelled"
+                        } else {
+                            "killed"
+                        };
+                        input.finish(process.history(), status.into());
+                    }
+
                     backend.close();
                 }
 
                 Command::none()
             }
-            Message::Event(_) => Command::none(),
-            Message::Process(message) => {
-                if let Self::Running { backend, process } = self {
-                    process.update(message, backend);
+            Message::Event(
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code,
+                    modifiers,
+                }),
+                status,
+            ) => self.shortcut(key_code, modifiers, status),
+            Message::Event(..) => Command::none(),
+            Message::Shortcut(key_code, modifiers, status, focused) => {
+                self.focused_shortcut(key_code, modifiers, status, focused);
+
+                Command::none()
+            }
+            Message::MaxParallel(value) => {
+                if let Self::Running {
+                    backend,
+                    max_parallel,
+                    ..
+                } = self
+                {
+                    match value.parse() {
+                        Ok(max) if max > 0 => {
+                            backend.set_max_parallel(max);
+                            *max_parallel = value;
+                        }
+                        Err(_) if value.is_empty() => *max_parallel = value,
+                        _ => {}
+                    }
+                }
+
+                Command::none()
+            }
+            Message::Input(message) => {
+                if let Self::Running {
+                    backend,
+                    input,
+                    processes,
+                    selected,
+                    terminal_size,
+                    ..
+                } = self
+                {
+                    if let Some(event) = input.update(message) {
+                        match event {
+                            input::Event::Submit {
+                                command,
+                                parsed,
+                                mut options,
+                                pty,
+                                history,
+                            } => {
+                                options.pty = pty.then_some(*terminal_size);
+
+                                let process =
+                                    Process::spawn(command, parsed, options, history, backend);
+
+                                // Failed to start, so it's already finished
+                                if process.id().is_none() {
+                                    input.finish(history, process.status());
+                                }
+
+                                processes.push(process);
+                                *selected = Some(processes.len() - 1);
+                            }
+                        }
+                    }
+                }
+
+                Command::none()
+            }
+            Message::Process(index, message) => {
+                if let Self::Running {
+                    backend,
+                    processes,
+                    selected,
+                    terminal_size,
+                    ..
+                } = self
+                {
+                    if let Some(process) = processes.get_mut(index) {
+                        if let Some(event) = process.update(message, backend) {
+                            match event {
+                                process::Event::Select => {
+                                    *selected = Some(index);
+
+                                    if let Some(id) = processes[index].following() {
+                                        return scrollable::snap_to(id, RelativeOffset::END);
+                                    }
+                                }
+                                process::Event::Scroll(id, offset) => {
+                                    return scrollable::snap_to(id, offset);
+                                }
+                                process::Event::Copy(contents) => {
+                                    return clipboard::write(contents);
+                                }
+                                process::Event::Resized(size) => {
+                                    *terminal_size = size;
+                                }
+                                process::Event::Save(path, bytes) => {
+                                    let history = processes[index].history();
+
+                                    // Up to a few buffers' worth, so off the UI thread
+                                    return Command::perform(
+                                        async move {
+                                            tokio::fs::write(&path, bytes)
+                                                .await
+                                                .map(|()| path)
+                                                .map_err(|err| err.to_string())
+                                        },
+                                        move |result| Message::Saved(history, result),
+                                    );
+                                }
+                                process::Event::MoveUp | process::Event::MoveDown => {
+                                    let other = if let process::Event::MoveUp = event {
+                                        processes[..index].iter().rposition(Process::is_queued)
+                                    } else {
+                                        processes[index + 1..]
+                                            .iter()
+                                            .position(Process::is_queued)
+                                            .map(|offset| index + 1 + offset)
+                                    };
+
+                                    // The backend swaps them in its queue too, so
+                                    // queued jobs start in the order they're listed
+                                    if let Some(other) = other {
+                                        if let (Some(a), Some(b)) =
+                                            (processes[index].id(), processes[other].id())
+                                        {
+                                            backend.swap(a, b);
+                                        }
+                                        processes.swap(index, other);
+
+                                        *selected = selected.map(|selected| match selected {
+                                            selected if selected == index => other,
+                                            selected if selected == other => index,
+                                            selected => selected,
+                                        });
+                                    }
+                                }
+                                process::Event::Remove => {
+                                    processes.remove(index);
+
+                                    *selected = match *selected {
+                                        Some(selected) if selected > index => Some(selected - 1),
+                                        Some(selected) if selected == index => index
+                                            .checked_sub(1)
+                                            .or_else(|| (!processes.is_empty()).then_some(0)),
+                                        selected => selected,
+                                    };
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Command::none()
+            }
+            Message::Saved(history, result) => {
+                if let Self::Running {
+                    backend, processes, ..
+                } = self
+                {
+                    if let Some(process) = processes
+                        .iter_mut()
+                        .find(|process| process.history() == history)
+                    {
+                        process.update(process::Message::Saved(result), backend);
+                    }
                 }
 
                 Command::none()
             }
             Message::Backend(message) => match message {
-                backend::Message::Setup(backend) => {
+                backend::Event::Setup(backend) => {
                     *self = Self::Running {
                         backend,
-                        process: Process::default(),
+                        input: Input::new(History::load()),
+                        processes: vec![],
+                        selected: None,
+                        // Until a terminal pane reports the size it's laid out at
+                        terminal_size: backend::Size { rows: 24, cols: 80 },
+                        max_parallel: backend::Settings::default().max_parallel.to_string(),
                     };
 
                     Command::none()
                 }
-                backend::Message::ProcessExited(_id, exited) => {
-                    if let Self::Running { process, .. } = self {
-                        process.exited(exited);
+                backend::Event::Spawned(id, result) => {
+                    if let Self::Running {
+                        input, processes, ..
+                    } = self
+                    {
+                        if let Some(process) = processes
+                            .iter_mut()
+                            .find(|process| process.id() == Some(id))
+                        {
+                            process.spawned(result);
+
+                            // A job that couldn't spawn won't report an exit
+                            if !process.is_active() {
+                                input.finish(process.history(), process.status());
+                            }
+                        }
+                    }
+
+                    Command::none()
+                }
+                backend::Event::Cancelled(id) => {
+                    if let Self::Running {
+                        input, processes, ..
+                    } = self
+                    {
+                        if let Some(process) = processes
+                            .iter_mut()
+                            .find(|process| process.id() == Some(id))
+                        {
+                            process.cancelled();
+                            input.finish(process.history(), process.status());
+                        }
+                    }
+
+                    Command::none()
+                }
+                backend::Event::Output(id, stream, line) => {
+                    if let Some(process) = self.process_mut(id) {
+                        process.output(stream, line);
+
+                        if let Some(id) = process.following() {
+                            return scrollable::snap_to(id, RelativeOffset::END);
+                        }
+                    }
+
+                    Command::none()
+                }
+                backend::Event::Terminal(id, bytes) => {
+                    if let Self::Running {
+                        backend, processes, ..
+                    } = self
+                    {
+                        if let Some(process) = processes
+                            .iter_mut()
+                            .find(|process| process.id() == Some(id))
+                        {
+                            process.terminal(&bytes, backend);
+                        }
+                    }
+
+                    Command::none()
+                }
+                backend::Event::ProcessExited(id, exited) => {
+                    if let Self::Running {
+                        input, processes, ..
+                    } = self
+                    {
+                        if let Some(process) = processes
+                            .iter_mut()
+                            .find(|process| process.id() == Some(id))
+                        {
+                            process.exited(exited);
+                            input.finish(process.history(), process.status());
+                        }
                     }
 
                     Command::none()
                 }
-                backend::Message::Closed => window::close(),
+                backend::Event::Closed => window::close(),
             },
         }
     }
@@ -89,329 +381,4185 @@ impl Application for App {
     fn view(&self) -> Element<Message> {
         match self {
             App::Idle => column![].into(),
-            App::Running { process, .. } => container(process.view().map(Message::Process))
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .padding(30)
-                .center_x()
-                .into(),
+            App::Running {
+                input,
+                processes,
+                selected,
+                max_parallel,
+                ..
+            } => {
+                let queue = row![
+                    text("Jobs").width(Length::Fill),
+                    text("Max parallel"),
+                    text_input("", max_parallel, Message::MaxParallel)
+                        .padding(5)
+                        .width(Length::Units(40)),
+                ]
+                .spacing(5)
+                .align_items(Alignment::Center);
+
+                let jobs = scrollable(
+                    column(
+                        processes
+                            .iter()
+                            .enumerate()
+                            .map(|(index, process)| {
+                                process
+                                    .entry(*selected == Some(index))
+                                    .map(move |message| Message::Process(index, message))
+                            })
+                            .collect(),
+                    )
+                    .spacing(5),
+                );
+
+                let output: Element<_> = match selected.and_then(|index| {
+                    processes
+                        .get(index)
+                        .map(|process| process.view().map(move |m| Message::Process(index, m)))
+                }) {
+                    Some(view) => view,
+                    None => text("No process selected").into(),
+                };
+
+                let content = column![
+                    input.view().map(Message::Input),
+                    row![
+                        column![queue, jobs]
+                            .spacing(5)
+                            .width(Length::FillPortion(1)),
+                        container(output).width(Length::FillPortion(3)),
+                    ]
+                    .spacing(10)
+                    .height(Length::Fill),
+                ]
+                .spacing(10);
+
+                container(content)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .padding(30)
+                    .into()
+            }
         }
     }
 }
 
-mod process {
-    use std::io;
+impl App {
+    fn shortcut(
+        &mut self,
+        key_code: keyboard::KeyCode,
+        modifiers: keyboard::Modifiers,
+        status: event::Status,
+    ) -> Command<Message> {
+        let Self::Running { input, .. } = self else {
+            return Command::none();
+        };
+
+        match (key_code, modifiers.control()) {
+            // Only apply to some inputs, so find out which one has focus first
+            (keyboard::KeyCode::D, true) | (keyboard::KeyCode::Up | keyboard::KeyCode::Down, _) => {
+                return focus::find()
+                    .map(move |focused| Message::Shortcut(key_code, modifiers, status, focused));
+            }
+            (keyboard::KeyCode::R, true) => {
+                input.update(input::Message::OpenSearch);
+
+                return text_input::focus(input::search_id());
+            }
+            (keyboard::KeyCode::Escape, _) if input.is_searching() => {
+                input.update(input::Message::CloseSearch);
+            }
+            _ => {}
+        }
+
+        Command::none()
+    }
+
+    fn focused_shortcut(
+        &mut self,
+        key_code: keyboard::KeyCode,
+        modifiers: keyboard::Modifiers,
+        status: event::Status,
+        focused: Option<widget::Id>,
+    ) {
+        let Self::Running {
+            backend,
+            input,
+            processes,
+            selected,
+            ..
+        } = self
+        else {
+            return;
+        };
+
+        let is_focused = |id: text_input::Id| focused == Some(id.into());
+        // Nothing has focus, so the key isn't meant for an input
+        let unfocused = focused.is_none() && matches!(status, event::Status::Ignored);
+
+        let recall = unfocused || is_focused(input::command_id()) || is_focused(input::search_id());
+        let stdin = unfocused || is_focused(input::command_id()) || is_focused(process::stdin_id());
+
+        match (key_code, modifiers.control()) {
+            (keyboard::KeyCode::D, true) if stdin => {
+                if let Some(process) = selected.and_then(|index| processes.get_mut(index)) {
+                    process.update(process::Message::CloseStdin, backend);
+                }
+            }
+            (keyboard::KeyCode::Up, _) if recall => {
+                input.update(input::Message::Previous);
+            }
+            (keyboard::KeyCode::Down, _) if recall => {
+                input.update(input::Message::Next);
+            }
+            _ => {}
+        }
+    }
+
+    fn process_mut(&mut self, id: JobId) -> Option<&mut Process> {
+        match self {
+            App::Idle => None,
+            App::Running { processes, .. } => processes
+                .iter_mut()
+                .find(|process| process.id() == Some(id)),
+        }
+    }
+}
+
+mod focus {
+    use iced::Command;
+    use iced_native::widget::operation::{Focusable, Operation, Outcome};
+    use iced_native::widget::Id;
+
+    /// Finds the widget with focus, if any
+    pub fn find() -> Command<Option<Id>> {
+        Command::widget(Find(None))
+    }
+
+    struct Find(Option<Id>);
+
+    impl Operation<Option<Id>> for Find {
+        fn container(
+            &mut self,
+            _id: Option<&Id>,
+            operate_on_children: &mut dyn FnMut(&mut dyn Operation<Option<Id>>),
+        ) {
+            operate_on_children(self);
+        }
+
+        fn focusable(&mut self, state: &mut dyn Focusable, id: Option<&Id>) {
+            if state.is_focused() {
+                self.0 = id.cloned();
+            }
+        }
+
+        // Also reports when nothing has focus, so the caller always hears back
+        fn finish(&self) -> Outcome<Option<Id>> {
+            Outcome::Some(self.0.clone())
+        }
+    }
+}
+
+mod input {
+    use std::time::Duration;
+
+    use iced::widget::{button, checkbox, column, container, row, scrollable, text, text_input};
+    use iced::{theme, Alignment, Color, Element, Length};
+    use process_backend::shell::{self, Pipeline};
+    use process_backend::SpawnOptions;
 
-    use iced::widget::{button, column, container, row, scrollable, text, text_input};
-    use iced::{Alignment, Element, Length};
+    use crate::history::{self, History};
+    use crate::options::{self, Options};
 
-    use crate::backend::{Backend, Exited};
+    /// Number of search results shown
+    const RESULTS: usize = 10;
+    const ERROR_COLOR: Color = Color::from_rgb(0.8, 0.25, 0.25);
+
+    pub fn command_id() -> text_input::Id {
+        text_input::Id::new("command")
+    }
+
+    pub fn search_id() -> text_input::Id {
+        text_input::Id::new("history-search")
+    }
 
     #[derive(Debug, Clone)]
     pub enum Message {
         Input(String),
+        ToggleShell(bool),
+        TogglePty(bool),
+        Timeout(String),
+        ToggleOptions,
+        Options(options::Message),
         Run,
-        Reset,
+        Previous,
+        Next,
+        OpenSearch,
+        CloseSearch,
+        Search(String),
+        Pick(usize),
+        PickSelected,
     }
 
     #[derive(Debug)]
-    pub enum Process {
-        Idle(String),
-        Running(u32, String),
-        Exited(String, Exited),
-        Error(String, String),
+    pub enum Event {
+        Submit {
+            command: String,
+            parsed: Result<Pipeline, shell::Error>,
+            options: SpawnOptions,
+            /// Run in a pseudo-terminal, sized by the app
+            pty: bool,
+            /// Index of the run in the history, see [`Input::finish`]
+            history: usize,
+        },
     }
 
-    impl Default for Process {
-        fn default() -> Self {
-            Self::Idle(String::new())
-        }
+    #[derive(Debug, Default)]
+    pub struct Input {
+        command: String,
+        shell: bool,
+        pty: bool,
+        /// Timeout in seconds, empty for none
+        timeout: String,
+        options: Options,
+        show_options: bool,
+        history: History,
+        /// Why the last finished command couldn't be saved
+        history_error: Option<String>,
+        recall: Option<Recall>,
+        search: Option<Search>,
     }
 
-    impl Process {
-        pub fn exited(&mut self, result: io::Result<Exited>) {
-            if let Self::Running(_, command) = self {
-                let command = std::mem::take(command);
+    /// Position while stepping through the history with Up / Down
+    #[derive(Debug)]
+    struct Recall {
+        index: usize,
+        /// What was typed before recalling, restored past the newest entry
+        draft: String,
+    }
 
-                match result {
-                    Ok(exited) => *self = Self::Exited(command, exited),
-                    Err(err) => *self = Self::Error(command, err.to_string()),
-                }
+    #[derive(Debug, Default)]
+    struct Search {
+        query: String,
+        results: Vec<usize>,
+        selected: usize,
+    }
+
+    impl Input {
+        pub fn new(history: History) -> Self {
+            Self {
+                history,
+                ..Self::default()
             }
         }
 
-        pub fn update(&mut self, message: Message, backend: &Backend) {
+        /// Records how a submitted command finished
+        pub fn finish(&mut self, history: usize, status: String) {
+            self.history_error = self.history.finish(history, status).err();
+        }
+
+        pub fn is_searching(&self) -> bool {
+            self.search.is_some()
+        }
+
+        pub fn update(&mut self, message: Message) -> Option<Event> {
             match message {
                 Message::Input(input) => {
-                    if let Self::Idle(command) = self {
-                        *command = input;
+                    self.command = input;
+                    self.recall = None;
+                    None
+                }
+                Message::ToggleShell(shell) => {
+                    self.shell = shell;
+                    None
+                }
+                Message::TogglePty(pty) => {
+                    self.pty = pty;
+                    None
+                }
+                Message::Timeout(timeout) => {
+                    if timeout.is_empty() || timeout.parse::<f64>().is_ok() {
+                        self.timeout = timeout;
                     }
+                    None
+                }
+                Message::ToggleOptions => {
+                    self.show_options = !self.show_options;
+                    None
+                }
+                Message::Options(message) => {
+                    self.options.update(message);
+                    None
                 }
                 Message::Run => {
-                    if let Self::Idle(command) = self {
-                        match backend.spawn(command) {
-                            Ok(Some(id)) => {
-                                *self = Self::Running(id, command.to_string());
-                            }
-                            Ok(None) => {
-                                *self =
-                                    Self::Error(std::mem::take(command), "Unknown Error".into());
-                            }
-                            Err(err) => {
-                                *self = Self::Error(std::mem::take(command), err.to_string());
-                            }
-                        }
+                    // Nothing to run, nor worth keeping in the history
+                    if self.command.trim().is_empty() {
+                        return None;
+                    }
+
+                    let parsed = if self.shell {
+                        shell::wrap(&self.command)
+                    } else {
+                        shell::parse(&self.command)
+                    };
+
+                    let options = SpawnOptions {
+                        timeout: self
+                            .timeout
+                            .parse()
+                            .ok()
+                            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                            .filter(|timeout| !timeout.is_zero()),
+                        ..self.options.spawn_options()
+                    };
+
+                    let command = std::mem::take(&mut self.command);
+                    let cwd = options
+                        .cwd
+                        .as_ref()
+                        .map(|cwd| cwd.display().to_string())
+                        .unwrap_or_default();
+                    let history = self.history.push(history::Entry::new(command.clone(), cwd));
+
+                    self.recall = None;
+                    // Results are indices, which the push may have shifted
+                    if let Some(search) = &mut self.search {
+                        search.results = self.history.search(&search.query);
+                        search.selected = 0;
                     }
+
+                    Some(Event::Submit {
+                        command,
+                        parsed,
+                        options,
+                        pty: self.pty,
+                        history,
+                    })
                 }
-                Message::Reset => {
-                    *self = Self::default();
+                Message::Previous => {
+                    if let Some(search) = &mut self.search {
+                        search.selected = search.selected.saturating_sub(1);
+                        return None;
+                    }
+
+                    let index = match &self.recall {
+                        Some(recall) => recall.index.checked_sub(1)?,
+                        None => self.history.entries().len().checked_sub(1)?,
+                    };
+
+                    self.recall(index);
+                    None
                 }
-            }
-        }
+                Message::Next => {
+                    if let Some(search) = &mut self.search {
+                        search.selected =
+                            (search.selected + 1).min(search.results.len().saturating_sub(1));
+                        return None;
+                    }
 
-        fn command(&self) -> &str {
-            match self {
-                Process::Idle(command) => command,
-                Process::Running(_, command) => command,
-                Process::Exited(command, _) => command,
-                Process::Error(command, _) => command,
+                    let index = self.recall.as_ref()?.index + 1;
+
+                    if index < self.history.entries().len() {
+                        self.recall(index);
+                    } else if let Some(recall) = self.recall.take() {
+                        self.command = recall.draft;
+                    }
+                    None
+                }
+                Message::OpenSearch => {
+                    self.search = Some(Search {
+                        results: self.history.search(""),
+                        ..Search::default()
+                    });
+                    None
+                }
+                Message::CloseSearch => {
+                    self.search = None;
+                    None
+                }
+                Message::Search(query) => {
+                    if let Some(search) = &mut self.search {
+                        search.results = self.history.search(&query);
+                        search.query = query;
+                        search.selected = 0;
+                    }
+                    None
+                }
+                Message::Pick(index) => {
+                    if let Some(entry) = self.history.entries().get(index) {
+                        self.command = entry.command.clone();
+                        self.recall = None;
+                    }
+                    self.search = None;
+                    None
+                }
+                Message::PickSelected => {
+                    let index = self
+                        .search
+                        .as_ref()
+                        .and_then(|search| search.results.get(search.selected).copied());
+
+                    match index {
+                        Some(index) => self.update(Message::Pick(index)),
+                        None => {
+                            self.search = None;
+                            None
+                        }
+                    }
+                }
             }
         }
 
-        fn active_input(&self) -> Element<Message> {
-            row![
-                container(
-                    text_input("Command...", self.command(), Message::Input)
-                        .on_submit(Message::Run)
-                        .padding(5)
-                )
-                .width(Length::Fill)
-                .max_width(400),
-                button(text("Run")).on_press(Message::Run),
-            ]
-            .spacing(5)
-            .align_items(Alignment::Center)
-            .into()
-        }
+        fn recall(&mut self, index: usize) {
+            let Some(entry) = self.history.entries().get(index) else {
+                return;
+            };
 
-        fn inactive_input(&self) -> Element<Message> {
-            row![
-                container(text_input(self.command(), "", Message::Input).padding(5))
-                    .width(Length::Fill)
-                    .max_width(400),
-                button(text("Run")),
-            ]
-            .spacing(5)
-            .align_items(Alignment::Center)
-            .into()
+            let draft = match self.recall.take() {
+                Some(recall) => recall.draft,
+                None => std::mem::take(&mut self.command),
+            };
+
+            self.command = entry.command.clone();
+            self.recall = Some(Recall { index, draft });
         }
 
-        fn reset_input(&self) -> Element<Message> {
-            row![
-                container(text_input(self.command(), "", Message::Input).padding(5))
-                    .width(Length::Fill)
-                    .max_width(400),
-                button(text("Reset")).on_press(Message::Reset),
+        fn search_view<'a>(&'a self, search: &'a Search) -> Element<'a, Message> {
+            let results = search
+                .results
+                .iter()
+                .take(RESULTS)
+                .enumerate()
+                .filter_map(|(position, index)| {
+                    let entry = self.history.entries().get(*index)?;
+                    let style = if position == search.selected {
+                        theme::Button::Primary
+                    } else {
+                        theme::Button::Secondary
+                    };
+
+                    Some(
+                        button(
+                            row![
+                                text(&entry.command).width(Length::Fill),
+                                text(entry.status.as_deref().unwrap_or("running")).size(16),
+                                text(&entry.cwd).size(16),
+                                text(entry.age()).size(16),
+                            ]
+                            .spacing(10)
+                            .align_items(Alignment::Center),
+                        )
+                        .width(Length::Fill)
+                        .style(style)
+                        .on_press(Message::Pick(*index))
+                        .into(),
+                    )
+                })
+                .collect();
+
+            column![
+                row![
+                    container(
+                        text_input("Search history...", &search.query, Message::Search)
+                            .id(search_id())
+                            .on_submit(Message::PickSelected)
+                            .padding(5)
+                    )
+                    .width(Length::Fill),
+                    button(text("Close")).on_press(Message::CloseSearch),
+                ]
+                .spacing(5)
+                .align_items(Alignment::Center),
+                scrollable(column(results).spacing(2)),
             ]
             .spacing(5)
-            .align_items(Alignment::Center)
             .into()
         }
 
         pub fn view(&self) -> Element<Message> {
-            match self {
-                Process::Idle(_) => self.active_input(),
-                Process::Running(_, _) => self.inactive_input(),
-                Process::Exited(_, exited) => {
-                    let input = self.reset_input();
-
-                    let status = text(format!("{}", exited.status));
-
-                    let output = scrollable(column(
-                        exited.stdout.lines().map(text).map(Element::from).collect(),
-                    ));
+            let input = row![
+                container(
+                    text_input("Command...", &self.command, Message::Input)
+                        .id(command_id())
+                        .on_submit(Message::Run)
+                        .padding(5)
+                )
+                .width(Length::Fill),
+                checkbox("Shell", self.shell, Message::ToggleShell),
+                checkbox("PTY", self.pty, Message::TogglePty),
+                text_input("Timeout (s)", &self.timeout, Message::Timeout)
+                    .on_submit(Message::Run)
+                    .padding(5)
+                    .width(Length::Units(100)),
+                button(text("Options")).on_press(Message::ToggleOptions),
+                button(text("Run")).on_press(Message::Run),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
 
-                    column![input, status, output]
-                        .align_items(Alignment::Center)
-                        .spacing(5)
-                        .into()
-                }
-                Process::Error(_, error) => {
-                    let input = self.reset_input();
+            let mut content = column![input].spacing(5);
 
-                    column![input, text(format!("ERROR: {error}"))]
-                        .align_items(Alignment::Center)
-                        .spacing(5)
-                        .into()
-                }
+            if let Some(error) = &self.history_error {
+                content = content.push(text(error).size(16).style(ERROR_COLOR));
+            }
+            if let Some(search) = &self.search {
+                content = content.push(self.search_view(search));
             }
+            if self.show_options {
+                content = content.push(self.options.view().map(Message::Options));
+            }
+
+            content.into()
         }
     }
 }
 
-mod backend {
-    use std::process::{ExitStatus, Stdio};
-    use std::time::Duration;
+mod options {
+    use std::path::PathBuf;
 
-    use iced::futures::stream::FuturesUnordered;
-    use iced::futures::{future, stream, FutureExt, StreamExt};
-    use iced::{subscription, Subscription};
-    use tokio::io::{AsyncReadExt, BufReader};
-    use tokio::process::{Child, Command};
-    use tokio::sync::mpsc::{self, Receiver, Sender};
-    use tokio::{io, time};
+    use iced::widget::{button, checkbox, column, container, row, text, text_input};
+    use iced::{theme, Alignment, Element, Length};
+    use process_backend::buffer::Limits;
+    use process_backend::SpawnOptions;
 
-    pub enum Event {
-        Wait(u32, Child),
-        Close,
-    }
+    const MIB: usize = 1024 * 1024;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     pub enum Message {
-        Setup(Backend),
-        ProcessExited(u32, io::Result<Exited>),
-        Closed,
+        Directory(String),
+        ClearEnv(bool),
+        Watch(String),
+        MaxLines(String),
+        MaxMib(String),
+        AddVar,
+        Key(usize, String),
+        Value(usize, String),
+        Unset(usize, bool),
+        RemoveVar(usize),
     }
 
-    pub enum Input {
-        Event(Event),
-        Process(u32, io::Result<ExitStatus>),
+    /// Working directory, environment & watched paths applied to every spawned command
+    #[derive(Debug, Default)]
+    pub struct Options {
+        directory: String,
+        clear_env: bool,
+        vars: Vec<Var>,
+        watch: String,
+        /// Output limits, the defaults are used while empty
+        max_lines: String,
+        max_mib: String,
     }
 
-    #[derive(Debug)]
-    pub struct Backend {
-        sender: Sender<Event>,
+    #[derive(Debug, Default)]
+    struct Var {
+        key: String,
+        value: String,
+        /// Remove the variable instead of setting it
+        unset: bool,
     }
 
-    impl Backend {
-        pub fn close(&self) {
-            let _ = self.sender.blocking_send(Event::Close);
+    impl Options {
+        pub fn update(&mut self, message: Message) {
+            match message {
+                Message::Directory(directory) => self.directory = directory,
+                Message::ClearEnv(clear_env) => self.clear_env = clear_env,
+                Message::Watch(watch) => self.watch = watch,
+                Message::MaxLines(max_lines) => {
+                    if max_lines.is_empty() || max_lines.parse::<usize>().is_ok() {
+                        self.max_lines = max_lines;
+                    }
+                }
+                Message::MaxMib(max_mib) => {
+                    if max_mib.is_empty() || max_mib.parse::<usize>().is_ok() {
+                        self.max_mib = max_mib;
+                    }
+                }
+                Message::AddVar => self.vars.push(Var::default()),
+                Message::Key(index, key) => {
+                    if let Some(var) = self.vars.get_mut(index) {
+                        var.key = key;
+                    }
+                }
+                Message::Value(index, value) => {
+                    if let Some(var) = self.vars.get_mut(index) {
+                        var.value = value;
+                    }
+                }
+                Message::Unset(index, unset) => {
+                    if let Some(var) = self.vars.get_mut(index) {
+                        var.unset = unset;
+                    }
+                }
+                Message::RemoveVar(index) => {
+                    if index < self.vars.len() {
+                        self.vars.remove(index);
+                    }
+                }
+            }
         }
 
-        pub fn spawn(&self, command: &str) -> io::Result<Option<u32>> {
-            let mut split = command.split(' ');
-
-            let program = split.next().unwrap_or_default();
-            let mut command = Command::new(program);
-            command.args(split);
-            command.stdout(Stdio::piped());
-
-            let child = command.spawn()?;
-            if let Some(id) = child.id() {
-                let _ = self.sender.blocking_send(Event::Wait(id, child));
+        pub fn spawn_options(&self) -> SpawnOptions {
+            let directory = self.directory.trim();
+            let default = Limits::default();
 
-                return Ok(Some(id));
+            SpawnOptions {
+                // Record where an inherited cwd points at, so runs can be reproduced
+                cwd: if directory.is_empty() {
+                    std::env::current_dir().ok()
+                } else {
+                    Some(PathBuf::from(directory))
+                },
+                clear_env: self.clear_env,
+                env: self
+                    .vars
+                    .iter()
+                    .filter(|var| !var.key.is_empty())
+                    .map(|var| {
+                        let value = (!var.unset).then(|| var.value.clone());
+                        (var.key.clone(), value)
+                    })
+                    .collect(),
+                watch: self.watch.split_whitespace().map(String::from).collect(),
+                limits: Limits {
+                    lines: self.max_lines.parse().unwrap_or(default.lines).max(1),
+                    bytes: self
+                        .max_mib
+                        .parse()
+                        .map_or(default.bytes, |mib: usize| mib.saturating_mul(MIB))
+                        .max(1),
+                },
+                ..SpawnOptions::default()
             }
-
-            Ok(None)
-        }
-    }
-
-    #[derive(Debug)]
-    pub struct Exited {
-        pub status: ExitStatus,
-        pub stdout: String,
-        pub stderr: String,
-    }
-
-    pub fn run() -> Subscription<Message> {
-        enum State {
-            Idle,
-            Running {
-                receiver: Receiver<Event>,
-                processes: Vec<(u32, Child)>,
-            },
-            Closed,
         }
 
-        subscription::unfold((), State::Idle, |state| async move {
-            match state {
-                State::Idle => {
-                    let (sender, receiver) = mpsc::channel(5);
+        pub fn view(&self) -> Element<Message> {
+            let directory = row![
+                text("Directory"),
+                container(text_input("Inherited", &self.directory, Message::Directory).padding(5))
+                    .width(Length::Fill),
+                checkbox("Clean environment", self.clear_env, Message::ClearEnv),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
 
-                    (
-                        Some(Message::Setup(Backend { sender })),
-                        State::Running {
-                            receiver,
-                            processes: vec![],
-                        },
+            let vars = self.vars.iter().enumerate().map(|(index, var)| {
+                let value: Element<_> = if var.unset {
+                    container(text("(unset)")).width(Length::Fill).into()
+                } else {
+                    container(
+                        text_input("Value", &var.value, move |value| {
+                            Message::Value(index, value)
+                        })
+                        .padding(5),
                     )
+                    .width(Length::Fill)
+                    .into()
+                };
+
+                row![
+                    container(
+                        text_input("Name", &var.key, move |key| Message::Key(index, key))
+                            .padding(5)
+                    )
+                    .width(Length::Units(200)),
+                    value,
+                    checkbox("Unset", var.unset, move |unset| Message::Unset(
+                        index, unset
+                    )),
+                    button(text("Remove"))
+                        .style(theme::Button::Destructive)
+                        .on_press(Message::RemoveVar(index)),
+                ]
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .into()
+            });
+
+            let watch = row![
+                text("Watch"),
+                text_input(
+                    "Paths or globs to rerun on, e.g. src/**/*.rs Cargo.toml",
+                    &self.watch,
+                    Message::Watch
+                )
+                .padding(5),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
+
+            let default = Limits::default();
+            let limits = row![
+                text("Keep the last"),
+                text_input(
+                    &default.lines.to_string(),
+                    &self.max_lines,
+                    Message::MaxLines
+                )
+                .padding(5)
+                .width(Length::Units(80)),
+                text("lines or"),
+                text_input(
+                    &(default.bytes / MIB).to_string(),
+                    &self.max_mib,
+                    Message::MaxMib
+                )
+                .padding(5)
+                .width(Length::Units(50)),
+                text("MiB of each stream"),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
+
+            let mut children = vec![directory.into(), watch.into(), limits.into()];
+            children.extend(vars);
+            children.push(
+                button(text("Add variable"))
+                    .on_press(Message::AddVar)
+                    .into(),
+            );
+
+            column(children).spacing(5).into()
+        }
+    }
+}
+
+mod history {
+    use std::collections::HashSet;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Entries kept in memory, older ones stay in the file
+    const LIMIT: usize = 1_000;
+
+    #[derive(Debug, Clone)]
+    pub struct Entry {
+        /// Seconds since the unix epoch
+        pub timestamp: u64,
+        pub cwd: String,
+        /// `None` until the command has finished
+        pub status: Option<String>,
+        pub command: String,
+    }
+
+    impl Entry {
+        pub fn new(command: String, cwd: String) -> Self {
+            Self {
+                timestamp: now(),
+                cwd,
+                status: None,
+                command,
+            }
+        }
+
+        /// How long ago the command was run, e.g. "5m ago"
+        pub fn age(&self) -> String {
+            match now().saturating_sub(self.timestamp) {
+                secs @ 0..=59 => format!("{secs}s ago"),
+                secs @ 60..=3599 => format!("{}m ago", secs / 60),
+                secs @ 3600..=86399 => format!("{}h ago", secs / 3600),
+                secs => format!("{}d ago", secs / 86400),
+            }
+        }
+
+        fn to_line(&self) -> String {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                self.timestamp,
+                escape(self.status.as_deref().unwrap_or("")),
+                escape(&self.cwd),
+                escape(&self.command)
+            )
+        }
+
+        fn from_line(line: &str) -> Option<Self> {
+            let mut fields = line.splitn(4, '\t');
+
+            let timestamp = fields.next()?.parse().ok()?;
+            let status = unescape(fields.next()?);
+            let cwd = unescape(fields.next()?);
+            let command = unescape(fields.next()?);
+
+            Some(Self {
+                timestamp,
+                cwd,
+                status: (!status.is_empty()).then_some(status),
+                command,
+            })
+        }
+    }
+
+    /// Commands are appended to `$XDG_DATA_HOME/child-processes/history`
+    /// as they finish, one tab separated `timestamp status cwd command`
+    /// line each
+    #[derive(Debug, Default)]
+    pub struct History {
+        entries: Vec<Entry>,
+        /// Entries trimmed off the front since loading, so the indices
+        /// handed out by [`History::push`] stay valid
+        dropped: usize,
+        path: Option<PathBuf>,
+    }
+
+    impl History {
+        pub fn load() -> Self {
+            let path = data_dir().map(|dir| dir.join("child-processes").join("history"));
+
+            let mut entries: Vec<Entry> = path
+                .as_ref()
+                .and_then(|path| fs::read_to_string(path).ok())
+                .map(|history| history.lines().filter_map(Entry::from_line).collect())
+                .unwrap_or_default();
+
+            entries.drain(..entries.len().saturating_sub(LIMIT));
+
+            Self {
+                entries,
+                dropped: 0,
+                path,
+            }
+        }
+
+        pub fn entries(&self) -> &[Entry] {
+            &self.entries
+        }
+
+        /// Adds an unfinished entry, returning its index for [`History::finish`].
+        /// Indices into [`History::entries`] shift once it's over the limit.
+        pub fn push(&mut self, entry: Entry) -> usize {
+            self.entries.push(entry);
+
+            let over = self.entries.len().saturating_sub(LIMIT);
+            self.entries.drain(..over);
+            self.dropped += over;
+
+            self.dropped + self.entries.len() - 1
+        }
+
+        /// Records the outcome of an entry and persists it
+        pub fn finish(&mut self, index: usize, status: String) -> Result<(), String> {
+            let Some(entry) = index
+                .checked_sub(self.dropped)
+                .and_then(|index| self.entries.get_mut(index))
+            else {
+                return Ok(());
+            };
+            if entry.status.is_some() {
+                return Ok(());
+            }
+            entry.status = Some(status);
+
+            match &self.path {
+                Some(path) => append(path, &entry.to_line())
+                    .map_err(|err| format!("Failed to save history to {}: {err}", path.display())),
+                None => Ok(()),
+            }
+        }
+
+        /// Indices of entries matching `query`, best match first. Only the
+        /// most recent run of each distinct command is included.
+        pub fn search(&self, query: &str) -> Vec<usize> {
+            let mut seen = HashSet::new();
+            let mut matches: Vec<(i64, usize)> = vec![];
+
+            for (index, entry) in self.entries.iter().enumerate().rev() {
+                if !seen.insert(entry.command.as_str()) {
+                    continue;
+                }
+
+                if let Some(score) = fuzzy_score(query, &entry.command) {
+                    matches.push((score, index));
+                }
+            }
+
+            // Stable sort, so equal scores stay most recent first
+            matches.sort_by_key(|(score, _)| -score);
+            matches.into_iter().map(|(_, index)| index).collect()
+        }
+    }
+
+    /// Scores `candidate` if it contains every character of `query` in
+    /// order (case-insensitive). Consecutive and word-start matches score
+    /// higher, gaps lower.
+    fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+        let candidate: Vec<char> = candidate.chars().collect();
+        let mut score = 0;
+        let mut position = 0;
+        let mut previous: Option<usize> = None;
+
+        for q in query.chars().flat_map(char::to_lowercase) {
+            let offset = candidate[position..]
+                .iter()
+                .position(|c| c.to_lowercase().eq(std::iter::once(q)))?;
+            let index = position + offset;
+
+            score += 1;
+            if previous == Some(index.wrapping_sub(1)) {
+                score += 5;
+            }
+            if index == 0 || !candidate[index - 1].is_alphanumeric() {
+                score += 3;
+            }
+            score -= offset.min(10) as i64;
+
+            previous = Some(index);
+            position = index + 1;
+        }
+
+        Some(score)
+    }
+
+    fn append(path: &PathBuf, line: &str) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(line.as_bytes())
+    }
+
+    fn data_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or_default()
+    }
+
+    fn escape(field: &str) -> String {
+        field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+    }
+
+    fn unescape(field: &str) -> String {
+        let mut unescaped = String::with_capacity(field.len());
+        let mut chars = field.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => {}
+            }
+        }
+
+        unescaped
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn round_trips_escaped_fields() {
+            for field in ["plain", "a\tb", "line\nbreak", "C:\\path\\t", "\\\t\\n"] {
+                assert!(!escape(field).contains(['\t', '\n']));
+                assert_eq!(unescape(&escape(field)), field);
+            }
+        }
+
+        #[test]
+        fn round_trips_entries() {
+            let entry = Entry {
+                timestamp: 42,
+                cwd: "/tmp/a\tb".into(),
+                status: Some("exit 1".into()),
+                command: "printf 'x\\ty\\n'".into(),
+            };
+
+            let line = entry.to_line();
+            let parsed = Entry::from_line(line.trim_end_matches('\n')).unwrap();
+
+            assert_eq!(parsed.timestamp, entry.timestamp);
+            assert_eq!(parsed.cwd, entry.cwd);
+            assert_eq!(parsed.status, entry.status);
+            assert_eq!(parsed.command, entry.command);
+        }
+
+        #[test]
+        fn scores_fuzzy_matches() {
+            // Case-insensitive, in order only
+            assert!(fuzzy_score("CB", "cargo build").is_some());
+            assert_eq!(fuzzy_score("bc", "cargo build"), None);
+            assert_eq!(fuzzy_score("xyz", "cargo build"), None);
+
+            // Consecutive matches beat gaps, word starts beat the middle of words
+            assert!(fuzzy_score("ca", "cargo") > fuzzy_score("ca", "chat"));
+            assert!(fuzzy_score("b", "x-build") > fuzzy_score("b", "xxbuild"));
+        }
+
+        #[test]
+        fn keeps_indices_valid_past_the_limit() {
+            let mut history = History::default();
+
+            let first = history.push(Entry::new("first".into(), String::new()));
+            for _ in 0..LIMIT {
+                history.push(Entry::new("later".into(), String::new()));
+            }
+            let last = history.push(Entry::new("last".into(), String::new()));
+
+            assert_eq!(history.entries().len(), LIMIT);
+            assert_eq!(last, LIMIT + 1);
+            assert_eq!(history.finish(first, "exit 0".into()), Ok(()));
+            assert_eq!(history.finish(last, "exit 1".into()), Ok(()));
+            assert_eq!(
+                history.entries().last().unwrap().status.as_deref(),
+                Some("exit 1")
+            );
+        }
+    }
+}
+
+mod process {
+    use std::borrow::Cow;
+    use std::fmt;
+    use std::io;
+    use std::path::PathBuf;
+
+    use iced::widget::scrollable::{self, RelativeOffset};
+    use iced::widget::{button, checkbox, column, container, pick_list, row, text, text_input};
+    use iced::{theme, Alignment, Color, Element, Length, Theme};
+    use process_backend::buffer::{Buffer, Encoding, Line};
+    use process_backend::shell::{self, Pipeline};
+    use process_backend::{
+        Backend, Exited, JobId, Outcome, Signal, Size, SpawnOptions, Stream, Termination, Usage,
+    };
+
+    use crate::find::{self, Find};
+    use crate::output::{self, Interleaved, Numbered, Source};
+    use crate::terminal::Terminal;
+
+    const STDERR_COLOR: Color = Color::from_rgb(0.9, 0.35, 0.35);
+    const TIMED_OUT_COLOR: Color = Color::from_rgb(0.9, 0.6, 0.1);
+    const RUNNING_COLOR: Color = Color::from_rgb(0.3, 0.5, 0.85);
+    const PAUSED_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+    const SUCCESS_COLOR: Color = Color::from_rgb(0.25, 0.65, 0.35);
+    const FAILURE_COLOR: Color = Color::from_rgb(0.8, 0.25, 0.25);
+    const SIGNALED_COLOR: Color = Color::from_rgb(0.6, 0.3, 0.7);
+    const STDIN_COLOR: Color = Color::from_rgb(0.45, 0.6, 0.9);
+
+    pub fn stdin_id() -> text_input::Id {
+        text_input::Id::new("stdin")
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Message {
+        Select,
+        Cancel,
+        MoveUp,
+        MoveDown,
+        Signal(Signal),
+        Stop,
+        Input(String),
+        Write,
+        CloseStdin,
+        Unwatch,
+        Encoding(Encoding),
+        Hex(bool),
+        Scrolled(RelativeOffset),
+        JumpToLatest,
+        Find(find::Message),
+        CopyStdout,
+        CopyAll,
+        SaveContents(Contents),
+        SavePath(String),
+        Save,
+        Saved(Result<PathBuf, String>),
+        /// The terminal pane was laid out at a new size
+        Resize(Size),
+        Remove,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Event {
+        Select,
+        /// Swap places with the closest queued job above
+        MoveUp,
+        /// Swap places with the closest queued job below
+        MoveDown,
+        Scroll(scrollable::Id, RelativeOffset),
+        /// Put text on the clipboard
+        Copy(String),
+        /// Write bytes to a file, reporting back with [`Message::Saved`]
+        Save(PathBuf, Vec<u8>),
+        /// Size of the terminal pane, for jobs started in one later
+        Resized(Size),
+        Remove,
+    }
+
+    #[derive(Debug)]
+    pub struct Process {
+        command: String,
+        state: State,
+        terminal: Option<Terminal>,
+        options: SpawnOptions,
+        history: usize,
+        /// Job that reruns when watched files change, until the user stops watching
+        watching: Option<JobId>,
+        /// Of the latest run's last stage, only shown for reference
+        pid: Option<u32>,
+        encoding: Encoding,
+        /// Show output as a hex dump, decided by sniffing it until the user picks
+        hex: Option<bool>,
+        /// Scrollables of the output panes, the live output or stdout & stderr
+        output_ids: [scrollable::Id; 2],
+        /// Keep the live output scrolled to the bottom, until the user scrolls up
+        follow: bool,
+        /// Lines that arrived since the user stopped following
+        unseen: usize,
+        find: Find,
+        export: Export,
+    }
+
+    /// Where "Save as…" writes, and what
+    #[derive(Debug, Default)]
+    struct Export {
+        contents: Contents,
+        path: String,
+        /// A save is being written
+        saving: bool,
+        /// Of the last save, until the path is edited
+        saved: Option<Result<PathBuf, String>>,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Contents {
+        Stdout,
+        Stderr,
+        /// A header describing the run, followed by both streams
+        #[default]
+        Transcript,
+    }
+
+    impl Contents {
+        const ALL: [Contents; 3] = [Contents::Stdout, Contents::Stderr, Contents::Transcript];
+    }
+
+    impl fmt::Display for Contents {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Contents::Stdout => write!(f, "stdout"),
+                Contents::Stderr => write!(f, "stderr"),
+                Contents::Transcript => write!(f, "transcript"),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    enum State {
+        Queued {
+            id: JobId,
+        },
+        Running {
+            id: JobId,
+            /// Live stdout & stderr, each capped at the output limits
+            streams: [Buffer<Numbered<Entry>>; 2],
+            /// Lines written to stdin, shown between the output
+            stdin: Buffer<Numbered<Entry>>,
+            /// Lines of output & input so far, numbering the next one
+            logged: usize,
+            paused: bool,
+            input: String,
+            stdin_open: bool,
+        },
+        Exited {
+            exited: Exited,
+            /// Lines written to stdin while it ran, the last ones within the
+            /// output limits
+            stdin: Vec<String>,
+            binary: bool,
+            /// Of the stdout & stderr panes, for the hex dump
+            offsets: [output::Offsets; 2],
+        },
+        Cancelled,
+        Error(String),
+    }
+
+    #[derive(Debug)]
+    enum Entry {
+        Input(String),
+        Output(Stream, Vec<u8>),
+    }
+
+    impl Line for Entry {
+        fn size(&self) -> usize {
+            match self {
+                Entry::Input(line) => line.len(),
+                Entry::Output(_, line) => line.len(),
+            }
+        }
+
+        fn text(&self, encoding: Encoding) -> Cow<'_, str> {
+            match self {
+                Entry::Input(line) => Cow::Owned(format!("> {line}")),
+                Entry::Output(_, line) => line.text(encoding),
+            }
+        }
+    }
+
+    impl Process {
+        pub fn spawn(
+            command: String,
+            parsed: Result<Pipeline, shell::Error>,
+            mut options: SpawnOptions,
+            history: usize,
+            backend: &Backend,
+        ) -> Self {
+            // The live buffers keep piped output, a terminal only keeps its screen
+            options.discard_output = options.pty.is_none();
+
+            let state = match parsed {
+                Ok(pipeline) => State::Queued {
+                    id: backend.spawn(pipeline, options.clone()),
+                },
+                Err(err) => State::Error(err.to_string()),
+            };
+
+            let watching = match state {
+                State::Queued { id } if !options.watch.is_empty() => Some(id),
+                _ => None,
+            };
+
+            Self {
+                command,
+                terminal: options
+                    .pty
+                    .filter(|_| matches!(state, State::Queued { .. }))
+                    .map(Terminal::new),
+                state,
+                options,
+                history,
+                watching,
+                pid: None,
+                encoding: Encoding::default(),
+                hex: None,
+                output_ids: [scrollable::Id::unique(), scrollable::Id::unique()],
+                follow: true,
+                unseen: 0,
+                find: Find::default(),
+                export: Export::default(),
+            }
+        }
+
+        /// Index of this run in the command history
+        pub fn history(&self) -> usize {
+            self.history
+        }
+
+        /// Id of the job while it's queued, running or watched
+        pub fn id(&self) -> Option<JobId> {
+            match self.state {
+                State::Queued { id } | State::Running { id, .. } => Some(id),
+                State::Exited { .. } | State::Cancelled | State::Error(_) => self.watching,
+            }
+        }
+
+        pub fn is_queued(&self) -> bool {
+            matches!(self.state, State::Queued { .. })
+        }
+
+        pub fn is_active(&self) -> bool {
+            matches!(self.state, State::Queued { .. } | State::Running { .. })
+        }
+
+        pub fn status(&self) -> String {
+            match &self.state {
+                State::Queued { .. } => "queued".into(),
+                State::Running { paused: true, .. } => "paused".into(),
+                State::Running { .. } => "running".into(),
+                State::Exited { exited, .. } => match exited.outcome {
+                    Outcome::Exited(termination) => termination.to_string(),
+                    Outcome::TimedOut { .. } => "timed out".into(),
+                },
+                State::Cancelled => "cancelled".into(),
+                State::Error(_) => "error".into(),
+            }
+        }
+
+        fn badge(&self) -> Element<Message> {
+            let color = match &self.state {
+                State::Queued { .. } | State::Cancelled => PAUSED_COLOR,
+                State::Running { paused: true, .. } => PAUSED_COLOR,
+                State::Running { .. } => RUNNING_COLOR,
+                State::Exited { exited, .. } => match exited.outcome {
+                    Outcome::Exited(Termination::Code(0)) => SUCCESS_COLOR,
+                    Outcome::Exited(Termination::Code(_)) => FAILURE_COLOR,
+                    Outcome::Exited(Termination::Signaled { .. }) => SIGNALED_COLOR,
+                    Outcome::Exited(Termination::Unknown(_)) => FAILURE_COLOR,
+                    Outcome::TimedOut { .. } => TIMED_OUT_COLOR,
+                },
+                State::Error(_) => FAILURE_COLOR,
+            };
+
+            container(text(self.status()).size(14).style(Color::WHITE))
+                .padding([2, 6])
+                .style(theme::Container::Custom(Box::new(Badge(color))))
+                .into()
+        }
+
+        pub fn output(&mut self, stream: Stream, line: Vec<u8>) {
+            if let State::Running {
+                streams, logged, ..
+            } = &mut self.state
+            {
+                let buffer = &mut streams[pane(stream)];
+
+                buffer.push(Numbered {
+                    seq: *logged,
+                    line: Entry::Output(stream, line),
+                });
+                *logged += 1;
+                self.find.scan(pane(stream), buffer, self.encoding);
+
+                if !self.follow {
+                    self.unseen += 1;
+                }
+            }
+        }
+
+        /// The live output to keep scrolled to the bottom, if following
+        pub fn following(&self) -> Option<scrollable::Id> {
+            match self.state {
+                State::Running { .. } if self.follow && self.terminal.is_none() => {
+                    Some(self.output_ids[0].clone())
+                }
+                _ => None,
+            }
+        }
+
+        pub fn terminal(&mut self, bytes: &[u8], backend: &Backend) {
+            if let (State::Running { id, .. }, Some(terminal)) = (&self.state, &mut self.terminal) {
+                if let Some(reply) = terminal.advance(bytes) {
+                    backend.write(*id, reply);
+                }
+            }
+        }
+
+        fn resize(&mut self, size: Size, backend: &Backend) {
+            if let (Some(id), Some(terminal)) = (self.id(), &mut self.terminal) {
+                terminal.resize(size);
+                backend.resize(id, size);
+
+                // Reruns start with a fresh terminal
+                self.options.pty = Some(size);
+            }
+        }
+
+        /// Also called when a watched command is rerun
+        pub fn spawned(&mut self, result: io::Result<u32>) {
+            let Some(id) = self
+                .id()
+                .filter(|_| !matches!(self.state, State::Running { .. }))
+            else {
+                return;
+            };
+
+            match result {
+                Ok(pid) => self.pid = Some(pid),
+                Err(err) => {
+                    self.state = State::Error(err.to_string());
+                    self.pid = None;
+                    // The backend stops watching a job that fails to start
+                    self.watching = None;
+                    return;
+                }
+            }
+
+            if let State::Exited { .. } | State::Error(_) = self.state {
+                self.terminal = self.options.pty.map(Terminal::new);
+            }
+            self.follow = true;
+            self.unseen = 0;
+
+            let limits = self.options.limits;
+            self.state = State::Running {
+                id,
+                streams: [Buffer::new(limits), Buffer::new(limits)],
+                stdin: Buffer::new(limits),
+                logged: 0,
+                paused: false,
+                input: String::new(),
+                stdin_open: true,
+            };
+            self.search();
+        }
+
+        pub fn cancelled(&mut self) {
+            if let State::Queued { .. } = self.state {
+                self.state = State::Cancelled;
+                self.watching = None;
+            }
+        }
+
+        pub fn exited(&mut self, result: io::Result<Box<Exited>>) {
+            if self.id().is_none() {
+                return;
+            }
+
+            let mut stdin = vec![];
+            let mut streams = [
+                Buffer::new(self.options.limits),
+                Buffer::new(self.options.limits),
+            ];
+            // The live buffers are the only copy of what was written & piped out
+            let state = std::mem::replace(&mut self.state, State::Cancelled);
+            if let State::Running {
+                streams: live,
+                stdin: written,
+                ..
+            } = state
+            {
+                for (buffer, live) in streams.iter_mut().zip(live) {
+                    buffer.add_truncated(live.truncated());
+
+                    for numbered in live {
+                        if let Entry::Output(_, line) = numbered.line {
+                            buffer.push(line);
+                        }
+                    }
+                }
+
+                for numbered in written {
+                    if let Entry::Input(line) = numbered.line {
+                        stdin.push(line);
+                    }
+                }
+            }
+
+            match result {
+                Ok(mut exited) => {
+                    if self.options.discard_output {
+                        [exited.stdout, exited.stderr] = streams;
+                    }
+
+                    self.state = State::Exited {
+                        binary: exited.stdout.is_binary() || exited.stderr.is_binary(),
+                        offsets: [
+                            output::Offsets::new(&exited.stdout),
+                            output::Offsets::new(&exited.stderr),
+                        ],
+                        exited: *exited,
+                        stdin,
+                    };
+                }
+                Err(err) => self.state = State::Error(err.to_string()),
+            }
+            self.search();
+        }
+
+        /// Runs the find bar's query over the output panes again
+        fn search(&mut self) {
+            match &self.state {
+                State::Running { streams, .. } => {
+                    self.find.search(&[&streams[0], &streams[1]], self.encoding)
+                }
+                State::Exited { exited, .. } => self
+                    .find
+                    .search(&[&exited.stdout, &exited.stderr], self.encoding),
+                _ => self.find.search::<Vec<u8>>(&[], self.encoding),
+            }
+        }
+
+        /// Scrolls to the find bar's current match
+        fn reveal(&mut self) -> Option<Event> {
+            let (pane, current) = self.find.current()?;
+            let (id, row, rows, truncated) = match &self.state {
+                State::Running { streams, stdin, .. } => {
+                    let live = self.live(streams, stdin);
+                    let row = live.row(pane, current.line)?;
+
+                    (0, row, live.rows(), live.truncated())
+                }
+                State::Exited { exited, .. } => {
+                    let output = match pane {
+                        0 => &exited.stdout,
+                        _ => &exited.stderr,
+                    };
+                    let (row, rows) = match self.find.highlights(pane).lines {
+                        Some(lines) => (
+                            lines.partition_point(|line| *line < current.line),
+                            lines.len(),
+                        ),
+                        None => (
+                            current.line.saturating_sub(output.truncated()),
+                            output.len(),
+                        ),
+                    };
+
+                    (pane, row, rows, output.truncated())
+                }
+                _ => return None,
+            };
+            let offset = output::offset(row, rows, truncated);
+
+            // Following the tail would scroll right past it
+            self.follow = false;
+
+            Some(Event::Scroll(self.output_ids[id].clone(), offset))
+        }
+
+        /// The live output, both streams & stdin interleaved as they came
+        fn live<'a>(
+            &'a self,
+            streams: &'a [Buffer<Numbered<Entry>>; 2],
+            stdin: &'a Buffer<Numbered<Entry>>,
+        ) -> Interleaved<'a, Entry> {
+            Interleaved::new(vec![
+                Source {
+                    buffer: &streams[0],
+                    highlights: Some(self.find.highlights(0)),
+                },
+                Source {
+                    buffer: &streams[1],
+                    highlights: Some(self.find.highlights(1)),
+                },
+                Source {
+                    buffer: stdin,
+                    highlights: None,
+                },
+            ])
+        }
+
+        pub fn update(&mut self, message: Message, backend: &Backend) -> Option<Event> {
+            match message {
+                Message::Select => Some(Event::Select),
+                Message::Cancel => {
+                    if let State::Queued { id } = self.state {
+                        backend.cancel(id);
+                    }
+                    None
+                }
+                Message::MoveUp => Some(Event::MoveUp),
+                Message::MoveDown => Some(Event::MoveDown),
+                Message::Signal(signal) => {
+                    if let State::Running { id, paused, .. } = &mut self.state {
+                        backend.signal(*id, signal);
+
+                        match signal {
+                            Signal::Stop => *paused = true,
+                            Signal::Continue => *paused = false,
+                            _ => {}
+                        }
+                    }
+                    None
+                }
+                Message::Stop => {
+                    if let State::Running { id, paused, .. } = &mut self.state {
+                        backend.terminate(*id);
+                        *paused = false;
+                    }
+                    None
+                }
+                Message::Input(value) => {
+                    if let State::Running { input, .. } = &mut self.state {
+                        *input = value;
+                    }
+                    None
+                }
+                Message::Write => {
+                    if let State::Running {
+                        id,
+                        stdin,
+                        logged,
+                        input,
+                        stdin_open: true,
+                        ..
+                    } = &mut self.state
+                    {
+                        let line = std::mem::take(input);
+
+                        // A terminal sends a carriage return for Enter
+                        if self.terminal.is_some() {
+                            backend.write(*id, format!("{line}\r"));
+                        } else {
+                            backend.write(*id, format!("{line}\n"));
+                            stdin.push(Numbered {
+                                seq: *logged,
+                                line: Entry::Input(line),
+                            });
+                            *logged += 1;
+                        }
+                    }
+                    None
+                }
+                Message::CloseStdin => {
+                    if let State::Running { id, stdin_open, .. } = &mut self.state {
+                        if *stdin_open {
+                            backend.close_stdin(*id);
+
+                            // The PTY only sees an EOF character, the
+                            // terminal stays writable
+                            *stdin_open = self.terminal.is_some();
+                        }
+                    }
+                    None
+                }
+                Message::Unwatch => {
+                    if let Some(id) = self.watching.take() {
+                        backend.unwatch(id);
+                    }
+                    None
+                }
+                Message::Encoding(encoding) => {
+                    self.encoding = encoding;
+                    self.search();
+                    None
+                }
+                Message::Hex(hex) => {
+                    self.hex = Some(hex);
+                    None
+                }
+                Message::Scrolled(offset) => {
+                    // Scrolling back down to the bottom resumes following
+                    self.follow = offset.y >= 0.999;
+                    if self.follow {
+                        self.unseen = 0;
+                    }
+                    None
+                }
+                Message::JumpToLatest => {
+                    self.follow = true;
+                    self.unseen = 0;
+                    Some(Event::Scroll(
+                        self.output_ids[0].clone(),
+                        RelativeOffset::END,
+                    ))
+                }
+                Message::Find(message) => match self.find.update(message)? {
+                    find::Event::Search => {
+                        self.search();
+                        self.reveal()
+                    }
+                    find::Event::Reveal => self.reveal(),
+                },
+                Message::CopyStdout => match &self.state {
+                    State::Exited { exited, .. } => {
+                        let stdout = exited.stdout.to_bytes();
+                        Some(Event::Copy(self.encoding.decode(&stdout).into_owned()))
+                    }
+                    _ => None,
+                },
+                Message::CopyAll => match &self.state {
+                    State::Exited { exited, stdin, .. } => {
+                        let transcript = self.transcript(exited, stdin);
+                        Some(Event::Copy(self.encoding.decode(&transcript).into_owned()))
+                    }
+                    _ => None,
+                },
+                Message::SaveContents(contents) => {
+                    self.export.contents = contents;
+                    None
+                }
+                Message::SavePath(path) => {
+                    self.export.path = path;
+                    self.export.saved = None;
+                    None
+                }
+                Message::Save => {
+                    let State::Exited { exited, stdin, .. } = &self.state else {
+                        return None;
+                    };
+                    if self.export.saving {
+                        return None;
+                    }
+
+                    // Written as is, whatever encoding it's displayed in
+                    let bytes = match self.export.contents {
+                        Contents::Stdout => exited.stdout.to_bytes(),
+                        Contents::Stderr => exited.stderr.to_bytes(),
+                        Contents::Transcript => self.transcript(exited, stdin),
+                    };
+
+                    self.export.saving = true;
+                    self.export.saved = None;
+
+                    Some(Event::Save(PathBuf::from(self.export.path.trim()), bytes))
+                }
+                Message::Saved(result) => {
+                    if self.export.saving {
+                        self.export.saving = false;
+                        self.export.saved = Some(result);
+                    }
+                    None
+                }
+                Message::Resize(size) => {
+                    self.resize(size, backend);
+                    Some(Event::Resized(size))
+                }
+                Message::Remove => {
+                    // Also drops a rerun that's waiting in the queue
+                    if let Some(id) = self.watching.take() {
+                        backend.cancel(id);
+                    }
+                    Some(Event::Remove)
+                }
+            }
+        }
+
+        pub fn entry(&self, selected: bool) -> Element<Message> {
+            let style = if selected {
+                theme::Button::Primary
+            } else {
+                theme::Button::Secondary
+            };
+
+            button(
+                row![text(&self.command).width(Length::Fill), self.badge()]
+                    .spacing(5)
+                    .align_items(Alignment::Center),
+            )
+            .width(Length::Fill)
+            .style(style)
+            .on_press(Message::Select)
+            .into()
+        }
+
+        fn header(&self) -> Element<Message> {
+            let remove = button(text("Remove")).style(theme::Button::Destructive);
+
+            let mut header = row![text(&self.command).width(Length::Fill)]
+                .spacing(5)
+                .align_items(Alignment::Center);
+
+            if let Some(pid) = self.pid {
+                header = header.push(text(format!("pid {pid}")).size(14));
+            }
+
+            // Cancelling the first run stops watching too
+            if self.watching.is_some() && !self.is_queued() {
+                header = header.push(button(text("Stop watching")).on_press(Message::Unwatch));
+            }
+
+            header
+                .push(if self.is_active() {
+                    remove
+                } else {
+                    remove.on_press(Message::Remove)
+                })
+                .into()
+        }
+
+        fn cwd(&self) -> String {
+            match &self.options.cwd {
+                Some(cwd) => format!("cwd: {}", cwd.display()),
+                None => "cwd: unknown".into(),
+            }
+        }
+
+        fn env(&self) -> String {
+            let env = self
+                .options
+                .env
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => format!("{key}={value}"),
+                    None => format!("-{key}"),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            match (self.options.clear_env, env.is_empty()) {
+                (true, true) => "env: clean".into(),
+                (true, false) => format!("env: clean + {env}"),
+                (false, true) => "env: inherited".into(),
+                (false, false) => format!("env: inherited + {env}"),
+            }
+        }
+
+        fn options_view(&self) -> Element<Message> {
+            let mut options = column![text(self.cwd()).size(16), text(self.env()).size(16)];
+
+            if !self.options.watch.is_empty() {
+                options =
+                    options.push(text(format!("watch: {}", self.options.watch.join(" "))).size(16));
+            }
+
+            options.into()
+        }
+
+        /// Header describing the run followed by what was written to stdin
+        /// and both streams, the output kept as raw bytes
+        fn transcript(&self, exited: &Exited, stdin: &[String]) -> Vec<u8> {
+            let status = match exited.outcome {
+                Outcome::Exited(termination) => match termination.hint() {
+                    Some(hint) => format!("{termination} ({hint})"),
+                    None => termination.to_string(),
+                },
+                Outcome::TimedOut { after } => format!("timed out after {after:?}"),
+            };
+
+            let mut header = format!(
+                "command: {}\n{}\n{}\nstatus: {status}\n",
+                self.command,
+                self.cwd(),
+                self.env()
+            );
+            if exited.stages.len() > 1 {
+                let stages = exited
+                    .stages
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+
+                header.push_str(&format!("stages: {stages}\n"));
+            }
+            header.push_str(&format!("took: {:.2?}\n", exited.usage.wall));
+
+            let mut transcript = header.into_bytes();
+            if !stdin.is_empty() {
+                transcript.extend_from_slice(b"\n--- stdin ---\n");
+                for line in stdin {
+                    transcript.extend_from_slice(line.as_bytes());
+                    transcript.push(b'\n');
+                }
+            }
+            for (title, output) in [("stdout", &exited.stdout), ("stderr", &exited.stderr)] {
+                let output = output.to_bytes();
+
+                transcript.extend_from_slice(format!("\n--- {title} ---\n").as_bytes());
+                transcript.extend_from_slice(&output);
+                if !output.is_empty() && !output.ends_with(b"\n") {
+                    transcript.push(b'\n');
+                }
+            }
+
+            transcript
+        }
+
+        /// Gets the output out, to the clipboard or a file
+        fn export_view(&self) -> Element<Message> {
+            let path = self.export.path.trim();
+            let save = button(text("Save as…"));
+
+            let mut export = row![
+                button(text("Copy stdout")).on_press(Message::CopyStdout),
+                button(text("Copy all")).on_press(Message::CopyAll),
+                pick_list(
+                    &Contents::ALL[..],
+                    Some(self.export.contents),
+                    Message::SaveContents
+                ),
+                container(
+                    text_input("Path...", &self.export.path, Message::SavePath)
+                        .on_submit(Message::Save)
+                        .padding(5)
+                )
+                .width(Length::Fill),
+                if path.is_empty() || self.export.saving {
+                    save
+                } else {
+                    save.on_press(Message::Save)
+                },
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
+
+            if self.export.saving {
+                export = export.push(text("Saving..."));
+            }
+            match &self.export.saved {
+                Some(Ok(path)) => {
+                    export = export.push(text(format!("Saved to {}", path.display())));
+                }
+                Some(Err(error)) => {
+                    export = export.push(text(format!("ERROR: {error}")).style(FAILURE_COLOR));
+                }
+                None => {}
+            }
+
+            export.into()
+        }
+
+        /// Picks how output is decoded, and whether it's shown as a hex
+        /// dump once it's complete
+        fn display_view(&self, hex: Option<bool>) -> Element<Message> {
+            let mut display = row![
+                text("Encoding"),
+                pick_list(&Encoding::ALL[..], Some(self.encoding), Message::Encoding),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center);
+
+            if let Some(hex) = hex {
+                display = display.push(checkbox("Hex dump", hex, Message::Hex));
+            }
+
+            display.into()
+        }
+
+        fn output_view<'a>(
+            &'a self,
+            stream: Stream,
+            output: &'a Buffer,
+            hex: Option<&'a output::Offsets>,
+        ) -> Element<'a, Message> {
+            let pane = pane(stream);
+            let title = match stream {
+                Stream::Stdout => text("stdout"),
+                Stream::Stderr => text("stderr").style(STDERR_COLOR),
+            };
+
+            let lines = match hex {
+                Some(offsets) => output::hex(output, offsets, stream_color(stream)),
+                None => output::view(
+                    output,
+                    self.encoding,
+                    move |_| stream_color(stream),
+                    self.find.highlights(pane),
+                ),
+            };
+
+            column![title, lines.id(self.output_ids[pane].clone())]
+                .spacing(5)
+                .height(Length::FillPortion(1))
+                .into()
+        }
+
+        pub fn view(&self) -> Element<Message> {
+            let header = self.header();
+
+            match &self.state {
+                State::Queued { .. } => {
+                    let controls = row![
+                        text("Queued").width(Length::Fill),
+                        button(text("Move up")).on_press(Message::MoveUp),
+                        button(text("Move down")).on_press(Message::MoveDown),
+                        button(text("Cancel"))
+                            .style(theme::Button::Destructive)
+                            .on_press(Message::Cancel),
+                    ]
+                    .spacing(5)
+                    .align_items(Alignment::Center);
+
+                    column![header, controls].spacing(5).into()
+                }
+                State::Running {
+                    streams,
+                    stdin,
+                    paused,
+                    input,
+                    stdin_open,
+                    ..
+                } => {
+                    let controls = row![
+                        text(match (paused, self.options.timeout) {
+                            (true, _) => "Paused".into(),
+                            (false, Some(timeout)) => format!("Running (timeout {timeout:?})..."),
+                            (false, None) => "Running...".into(),
+                        })
+                        .width(Length::Fill),
+                        button(text("Interrupt")).on_press(Message::Signal(Signal::Interrupt)),
+                        if *paused {
+                            button(text("Resume")).on_press(Message::Signal(Signal::Continue))
+                        } else {
+                            button(text("Pause")).on_press(Message::Signal(Signal::Stop))
+                        },
+                        button(text("Stop")).on_press(Message::Stop),
+                        button(text("Kill"))
+                            .style(theme::Button::Destructive)
+                            .on_press(Message::Signal(Signal::Kill)),
+                    ]
+                    .spacing(5)
+                    .align_items(Alignment::Center);
+
+                    let output = match &self.terminal {
+                        Some(terminal) => terminal.view().map(Message::Resize),
+                        None => {
+                            let mut display = row![self.display_view(None)]
+                                .spacing(5)
+                                .align_items(Alignment::Center);
+
+                            if !self.follow {
+                                display = display.push(
+                                    button(text(format!(
+                                        "Jump to latest ({} new lines)",
+                                        self.unseen
+                                    )))
+                                    .on_press(Message::JumpToLatest),
+                                );
+                            }
+
+                            let find = self.find.view().map(Message::Find);
+                            let lines = self
+                                .live(streams, stdin)
+                                .view(self.encoding, entry_color)
+                                .id(self.output_ids[0].clone())
+                                .on_scroll(Message::Scrolled);
+
+                            column![display, find, lines].spacing(5).into()
+                        }
+                    };
+
+                    let stdin: Element<_> = if *stdin_open {
+                        row![
+                            container(
+                                text_input("Input...", input, Message::Input)
+                                    .id(stdin_id())
+                                    .on_submit(Message::Write)
+                                    .padding(5)
+                            )
+                            .width(Length::Fill),
+                            button(text("Send")).on_press(Message::Write),
+                            button(text("EOF")).on_press(Message::CloseStdin),
+                        ]
+                        .spacing(5)
+                        .align_items(Alignment::Center)
+                        .into()
+                    } else {
+                        text("stdin closed").into()
+                    };
+
+                    column![header, controls, output, stdin].spacing(5).into()
+                }
+                State::Exited {
+                    exited,
+                    stdin,
+                    binary,
+                    offsets,
+                } => {
+                    let detail = match exited.outcome {
+                        Outcome::Exited(termination) => termination.hint(),
+                        Outcome::TimedOut { after } => Some(format!("after {after:?}")),
+                    };
+                    let mut status = row![self.badge(), text(detail.unwrap_or_default())]
+                        .spacing(5)
+                        .align_items(Alignment::Center);
+
+                    if exited.stages.len() > 1 {
+                        let stages = exited
+                            .stages
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" | ");
+
+                        status = status.push(text(format!("(stages: {stages})")));
+                    }
+
+                    let usage = usage_view(&exited.usage);
+                    let options = self.options_view();
+                    let export = self.export_view();
+
+                    if let Some(terminal) = &self.terminal {
+                        let terminal = terminal.view().map(Message::Resize);
+                        return column![header, status, usage, options, export, terminal]
+                            .spacing(5)
+                            .into();
+                    }
+
+                    let hex = self.hex.unwrap_or(*binary);
+                    let display = self.display_view(Some(hex));
+
+                    let [stdout, stderr] = offsets;
+                    let stdout =
+                        self.output_view(Stream::Stdout, &exited.stdout, hex.then_some(stdout));
+                    let stderr =
+                        self.output_view(Stream::Stderr, &exited.stderr, hex.then_some(stderr));
+
+                    let mut view = column![header, status, usage, options, export, display];
+                    // Matches are found in the decoded text, not the dump
+                    if !hex {
+                        view = view.push(self.find.view().map(Message::Find));
+                    }
+                    if !stdin.is_empty() {
+                        view = view.push(stdin_view(stdin));
+                    }
+
+                    view.push(stdout).push(stderr).spacing(5).into()
+                }
+                State::Cancelled => column![header, text("Cancelled before it started")]
+                    .spacing(5)
+                    .into(),
+                State::Error(error) => column![header, text(format!("ERROR: {error}"))]
+                    .spacing(5)
+                    .into(),
+            }
+        }
+    }
+
+    struct Badge(Color);
+
+    impl container::StyleSheet for Badge {
+        type Style = Theme;
+
+        fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+            container::Appearance {
+                background: Some(self.0.into()),
+                border_radius: 4.0,
+                ..Default::default()
+            }
+        }
+    }
+
+    fn usage_view(usage: &Usage) -> Element<Message> {
+        let Usage {
+            wall,
+            user,
+            system,
+            max_rss,
+        } = usage;
+
+        text(format!(
+            "took {wall:.2?} · user {user:.2?} · sys {system:.2?} · peak RSS {}",
+            bytes(*max_rss)
+        ))
+        .size(16)
+        .into()
+    }
+
+    fn bytes(bytes: u64) -> String {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+        let mut value = bytes as f64;
+        let mut unit = 0;
+
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            format!("{bytes} B")
+        } else {
+            format!("{value:.1} {}", UNITS[unit])
+        }
+    }
+
+    /// What was written to stdin, echoed like in the live output
+    fn stdin_view(stdin: &[String]) -> Element<Message> {
+        let lines = stdin.iter().fold(column![], |lines, line| {
+            lines.push(text(format!("> {line}")).style(STDIN_COLOR))
+        });
+
+        column![
+            text("stdin").style(STDIN_COLOR),
+            scrollable(lines).height(Length::Units(80))
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    fn entry_color(entry: &Entry) -> Color {
+        match entry {
+            Entry::Input(_) => STDIN_COLOR,
+            Entry::Output(stream, _) => stream_color(*stream),
+        }
+    }
+
+    /// Index of the stream's output pane
+    fn pane(stream: Stream) -> usize {
+        match stream {
+            Stream::Stdout => 0,
+            Stream::Stderr => 1,
+        }
+    }
+
+    fn stream_color(stream: Stream) -> Color {
+        match stream {
+            Stream::Stdout => Color::BLACK,
+            Stream::Stderr => STDERR_COLOR,
+        }
+    }
+}
+
+mod ansi {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Color {
+        Indexed(u8),
+        Rgb(u8, u8, u8),
+    }
+
+    impl From<Color> for iced::Color {
+        fn from(color: Color) -> Self {
+            match color {
+                Color::Indexed(index) => indexed(index),
+                Color::Rgb(r, g, b) => iced::Color::from_rgb8(r, g, b),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Style {
+        pub foreground: Option<Color>,
+        pub background: Option<Color>,
+        pub bold: bool,
+        pub underline: bool,
+        pub inverse: bool,
+    }
+
+    impl Style {
+        /// Resolves the foreground & background against the given defaults.
+        /// Bold text uses the bright variant of the 8 basic colors.
+        pub fn colors(
+            &self,
+            foreground: iced::Color,
+            background: iced::Color,
+        ) -> (iced::Color, Option<iced::Color>) {
+            let fg = match self.foreground {
+                Some(Color::Indexed(index @ 0..=7)) if self.bold => indexed(index + 8),
+                Some(color) => color.into(),
+                None => foreground,
+            };
+            let bg = self.background.map(iced::Color::from);
+
+            if self.inverse {
+                (bg.unwrap_or(background), Some(fg))
+            } else {
+                (fg, bg)
+            }
+        }
+
+        /// Applies an SGR (`CSI ... m`) parameter list. Both `;` and `:`
+        /// separated extended colors are understood.
+        pub fn apply(&mut self, params: &[&[u16]]) {
+            if params.is_empty() {
+                *self = Style::default();
+                return;
+            }
+
+            let mut params = params.iter();
+
+            while let Some(param) = params.next() {
+                match param[0] {
+                    0 => *self = Style::default(),
+                    1 => self.bold = true,
+                    4 => self.underline = true,
+                    7 => self.inverse = true,
+                    21 | 22 => self.bold = false,
+                    24 => self.underline = false,
+                    27 => self.inverse = false,
+                    n @ 30..=37 => self.foreground = Some(Color::Indexed(n as u8 - 30)),
+                    38 => self.foreground = extended(param, &mut params),
+                    39 => self.foreground = None,
+                    n @ 40..=47 => self.background = Some(Color::Indexed(n as u8 - 40)),
+                    48 => self.background = extended(param, &mut params),
+                    49 => self.background = None,
+                    n @ 90..=97 => self.foreground = Some(Color::Indexed(n as u8 - 90 + 8)),
+                    n @ 100..=107 => self.background = Some(Color::Indexed(n as u8 - 100 + 8)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Parses the color following a `38` / `48`, either from its `:`
+    /// sub-parameters or by consuming the following `;` parameters
+    fn extended<'a>(
+        param: &[u16],
+        params: &mut impl Iterator<Item = &'a &'a [u16]>,
+    ) -> Option<Color> {
+        let values: Vec<u16> = if param.len() > 1 {
+            param[1..].to_vec()
+        } else {
+            match params.next().map(|p| p[0]) {
+                Some(5) => params.next().map(|p| vec![5, p[0]]).unwrap_or_default(),
+                Some(2) => {
+                    let rgb: Vec<u16> = params.take(3).map(|p| p[0]).collect();
+                    [&[2], rgb.as_slice()].concat()
+                }
+                _ => vec![],
+            }
+        };
+
+        match values.as_slice() {
+            [5, index] => Some(Color::Indexed(*index as u8)),
+            // `38:2:<colorspace>:r:g:b` carries an extra colorspace id
+            [2, _, r, g, b] | [2, r, g, b] => Some(Color::Rgb(*r as u8, *g as u8, *b as u8)),
+            _ => None,
+        }
+    }
+
+    /// The xterm 256 color palette
+    fn indexed(index: u8) -> iced::Color {
+        const ANSI: [(u8, u8, u8); 16] = [
+            (0x00, 0x00, 0x00),
+            (0xcd, 0x31, 0x31),
+            (0x0d, 0xbc, 0x79),
+            (0xe5, 0xe5, 0x10),
+            (0x24, 0x72, 0xc8),
+            (0xbc, 0x3f, 0xbc),
+            (0x11, 0xa8, 0xcd),
+            (0xe5, 0xe5, 0xe5),
+            (0x66, 0x66, 0x66),
+            (0xf1, 0x4c, 0x4c),
+            (0x23, 0xd1, 0x8b),
+            (0xf5, 0xf5, 0x43),
+            (0x3b, 0x8e, 0xea),
+            (0xd6, 0x70, 0xd6),
+            (0x29, 0xb8, 0xdb),
+            (0xff, 0xff, 0xff),
+        ];
+
+        match index {
+            0..=15 => {
+                let (r, g, b) = ANSI[index as usize];
+                iced::Color::from_rgb8(r, g, b)
+            }
+            16..=231 => {
+                let index = index - 16;
+                let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+
+                iced::Color::from_rgb8(level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            232..=255 => {
+                let gray = 8 + (index - 232) * 10;
+                iced::Color::from_rgb8(gray, gray, gray)
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Span {
+        pub text: String,
+        pub style: Style,
+    }
+
+    /// Splits a line of output into styled spans. Only SGR sequences are
+    /// interpreted, every other escape or control sequence is dropped.
+    pub fn parse(line: &str) -> Vec<Span> {
+        let mut parser = vte::Parser::new();
+        let mut spans = Spans::default();
+
+        for byte in line.bytes() {
+            parser.advance(&mut spans, byte);
+        }
+
+        spans.spans
+    }
+
+    /// The text as it's displayed, without escapes
+    pub fn strip(line: &str) -> String {
+        parse(line).into_iter().map(|span| span.text).collect()
+    }
+
+    #[derive(Default)]
+    struct Spans {
+        spans: Vec<Span>,
+        style: Style,
+    }
+
+    impl vte::Perform for Spans {
+        fn print(&mut self, c: char) {
+            match self.spans.last_mut() {
+                Some(span) if span.style == self.style => span.text.push(c),
+                _ => self.spans.push(Span {
+                    text: c.to_string(),
+                    style: self.style,
+                }),
+            }
+        }
+
+        fn execute(&mut self, byte: u8) {
+            match byte {
+                b'\t' => self.print('\t'),
+                // Progress bars redraw the line after a carriage return
+                b'\r' => self.spans.clear(),
+                _ => {}
+            }
+        }
+
+        fn csi_dispatch(
+            &mut self,
+            params: &vte::Params,
+            intermediates: &[u8],
+            _ignore: bool,
+            action: char,
+        ) {
+            if action == 'm' && intermediates.is_empty() {
+                self.style.apply(&params.iter().collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn style(sgr: &str) -> Style {
+            parse(&format!("\x1b[{sgr}mx"))[0].style
+        }
+
+        #[test]
+        fn applies_basic_attributes() {
+            let styled = style("1;4;31;42");
+
+            assert!(styled.bold && styled.underline);
+            assert_eq!(styled.foreground, Some(Color::Indexed(1)));
+            assert_eq!(styled.background, Some(Color::Indexed(2)));
+            assert_eq!(style("1;31;0"), Style::default());
+            assert_eq!(style("95").foreground, Some(Color::Indexed(13)));
+        }
+
+        #[test]
+        fn applies_extended_colors() {
+            assert_eq!(style("38;5;196").foreground, Some(Color::Indexed(196)));
+            assert_eq!(style("48:5:17").background, Some(Color::Indexed(17)));
+            assert_eq!(
+                style("38;2;10;20;30").foreground,
+                Some(Color::Rgb(10, 20, 30))
+            );
+            assert_eq!(
+                style("38:2:10:20:30").foreground,
+                Some(Color::Rgb(10, 20, 30))
+            );
+            // With an empty colorspace id
+            assert_eq!(
+                style("38:2::10:20:30").foreground,
+                Some(Color::Rgb(10, 20, 30))
+            );
+
+            // The parameters after a `;` separated color are still applied
+            let style = style("38;5;2;1");
+            assert_eq!(style.foreground, Some(Color::Indexed(2)));
+            assert!(style.bold);
+        }
+
+        #[test]
+        fn splits_styled_spans() {
+            let spans = parse("plain \x1b[1mbold\x1b[0m plain");
+
+            assert_eq!(spans.len(), 3);
+            assert_eq!(spans[1].text, "bold");
+            assert!(spans[1].style.bold);
+            assert_eq!(strip("\x1b[32mok\x1b[0m\x1b[K"), "ok");
+            // Only what's drawn after the last carriage return is left
+            assert_eq!(strip("10%\r100%"), "100%");
+        }
+    }
+}
+
+mod find {
+    use std::ops::Range;
+
+    use iced::widget::{button, checkbox, container, row, text, text_input};
+    use iced::{Alignment, Color, Element, Length};
+    use process_backend::buffer::{Buffer, Encoding, Line};
+    use regex::Regex;
+
+    use crate::ansi;
+
+    const ERROR_COLOR: Color = Color::from_rgb(0.8, 0.25, 0.25);
+
+    #[derive(Debug, Clone)]
+    pub enum Message {
+        Query(String),
+        Regex(bool),
+        Filter(bool),
+        Next,
+        Previous,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum Event {
+        /// The query changed, run it over the output again
+        Search,
+        /// The current match moved, scroll to it
+        Reveal,
+    }
+
+    /// Matches of a query in the output panes, i.e. the live output or
+    /// stdout & stderr
+    #[derive(Debug, Default)]
+    pub struct Find {
+        query: String,
+        regex: bool,
+        /// Only show lines with a match, like `grep`
+        filter: bool,
+        /// Compiled from the query, none while it's empty or invalid
+        pattern: Option<Regex>,
+        error: Option<String>,
+        panes: [Vec<Match>; 2],
+        /// Index of the current match, counting through the panes in order
+        current: Option<usize>,
+    }
+
+    /// A match within a line's displayed text, i.e. without ANSI escapes
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Match {
+        /// Counted from the first line ever pushed, so truncation doesn't
+        /// shift it
+        pub line: usize,
+        pub range: Range<usize>,
+    }
+
+    impl Find {
+        pub fn update(&mut self, message: Message) -> Option<Event> {
+            match message {
+                Message::Query(query) => {
+                    self.query = query;
+                    self.compile();
+                    Some(Event::Search)
+                }
+                Message::Regex(regex) => {
+                    self.regex = regex;
+                    self.compile();
+                    Some(Event::Search)
+                }
+                Message::Filter(filter) => {
+                    self.filter = filter;
+                    Some(Event::Reveal)
+                }
+                Message::Next => {
+                    let count = self.count();
+                    if count == 0 {
+                        return None;
+                    }
+
+                    self.current = Some(self.current.map_or(0, |current| (current + 1) % count));
+                    Some(Event::Reveal)
+                }
+                Message::Previous => {
+                    let count = self.count();
+                    if count == 0 {
+                        return None;
+                    }
+
+                    self.current = Some(
+                        self.current
+                            .map_or(count - 1, |current| (current + count - 1) % count),
+                    );
+                    Some(Event::Reveal)
+                }
+            }
+        }
+
+        fn compile(&mut self) {
+            let pattern = if self.regex {
+                self.query.clone()
+            } else {
+                regex::escape(&self.query)
+            };
+
+            match Regex::new(&pattern) {
+                Ok(pattern) if !self.query.is_empty() => {
+                    self.pattern = Some(pattern);
+                    self.error = None;
+                }
+                Ok(_) => {
+                    self.pattern = None;
+                    self.error = None;
+                }
+                Err(err) => {
+                    self.pattern = None;
+                    self.error = Some(err.to_string());
+                }
+            }
+        }
+
+        /// Runs the query over every line of the panes, starting at the
+        /// first match
+        pub fn search<T: Line>(&mut self, buffers: &[&Buffer<T>], encoding: Encoding) {
+            self.panes = Default::default();
+
+            if let Some(pattern) = &self.pattern {
+                for (matches, buffer) in self.panes.iter_mut().zip(buffers) {
+                    for index in 0..buffer.len() {
+                        if let Some(line) = buffer.get(index) {
+                            let line = find(pattern, buffer.truncated() + index, line, encoding);
+                            matches.extend(line);
+                        }
+                    }
+                }
+            }
+
+            self.current = (self.count() > 0).then_some(0);
+        }
+
+        /// Runs the query over the line just pushed to a pane, and forgets
+        /// matches in lines that were dropped to make room for it
+        pub fn scan<T: Line>(&mut self, pane: usize, buffer: &Buffer<T>, encoding: Encoding) {
+            let Some(pattern) = &self.pattern else {
+                return;
+            };
+
+            let matches = &mut self.panes[pane];
+            let dropped = matches.partition_point(|found| found.line < buffer.truncated());
+            matches.drain(..dropped);
+
+            if let Some(line) = buffer
+                .len()
+                .checked_sub(1)
+                .and_then(|index| buffer.get(index))
+            {
+                let index = buffer.truncated() + buffer.len() - 1;
+                matches.extend(find(pattern, index, line, encoding));
+            }
+
+            let before = self.panes[..pane].iter().map(Vec::len).sum::<usize>();
+            self.current = match self.current {
+                Some(current) if current >= before => Some(current.saturating_sub(dropped)),
+                current => current,
+            }
+            .filter(|current| *current < self.count());
+        }
+
+        fn count(&self) -> usize {
+            self.panes.iter().map(Vec::len).sum()
+        }
+
+        /// Pane of the current match, and the match
+        pub fn current(&self) -> Option<(usize, &Match)> {
+            let mut current = self.current?;
+
+            for (pane, matches) in self.panes.iter().enumerate() {
+                match matches.get(current) {
+                    Some(found) => return Some((pane, found)),
+                    None => current -= matches.len(),
+                }
+            }
+
+            None
+        }
+
+        pub fn highlights(&self, pane: usize) -> Highlights<'_> {
+            let matches = &self.panes[pane];
+
+            let lines = (self.filter && self.pattern.is_some()).then(|| {
+                let mut lines = matches.iter().map(|found| found.line).collect::<Vec<_>>();
+                lines.dedup();
+                lines
+            });
+
+            Highlights {
+                matches,
+                current: self
+                    .current()
+                    .filter(|(current, _)| *current == pane)
+                    .map(|(_, found)| found),
+                lines,
+            }
+        }
+
+        pub fn view(&self) -> Element<Message> {
+            let count = self.count();
+
+            let status = match (&self.error, self.current) {
+                (Some(error), _) => {
+                    text(error.lines().last().unwrap_or(error.as_str())).style(ERROR_COLOR)
+                }
+                (None, _) if self.pattern.is_none() => text(""),
+                (None, _) if count == 0 => text("No matches"),
+                (None, Some(current)) => text(format!("{} of {count}", current + 1)),
+                (None, None) => text(format!("{count} matches")),
+            };
+
+            let previous = button(text("Previous"));
+            let next = button(text("Next"));
+            let (previous, next) = if count > 0 {
+                (
+                    previous.on_press(Message::Previous),
+                    next.on_press(Message::Next),
+                )
+            } else {
+                (previous, next)
+            };
+
+            row![
+                container(
+                    text_input("Find...", &self.query, Message::Query)
+                        .on_submit(Message::Next)
+                        .padding(5)
+                )
+                .width(Length::Fill),
+                checkbox("Regex", self.regex, Message::Regex),
+                checkbox("Filter", self.filter, Message::Filter),
+                status,
+                previous,
+                next,
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .into()
+        }
+    }
+
+    /// What a pane's view highlights, and filters by
+    #[derive(Debug)]
+    pub struct Highlights<'a> {
+        matches: &'a [Match],
+        current: Option<&'a Match>,
+        /// Lines with a match when filtering, in order
+        pub lines: Option<Vec<usize>>,
+    }
+
+    impl<'a> Highlights<'a> {
+        /// Ranges to highlight in a line, and whether each is the current match
+        pub fn line(&self, line: usize) -> Vec<(Range<usize>, bool)> {
+            let start = self.matches.partition_point(|found| found.line < line);
+
+            self.matches[start..]
+                .iter()
+                .take_while(|found| found.line == line)
+                .map(|found| (found.range.clone(), Some(found) == self.current))
+                .collect()
+        }
+    }
+
+    fn find<T: Line>(pattern: &Regex, index: usize, line: &T, encoding: Encoding) -> Vec<Match> {
+        let text = ansi::strip(&line.text(encoding));
+
+        pattern
+            .find_iter(&text)
+            .filter(|found| !found.range().is_empty())
+            .map(|found| Match {
+                line: index,
+                range: found.range(),
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use process_backend::buffer::Limits;
+
+        use super::*;
+
+        fn find(query: &str) -> Find {
+            let mut find = Find::default();
+            find.update(Message::Query(query.into()));
+            find
+        }
+
+        fn buffer(lines: usize) -> Buffer {
+            Buffer::new(Limits {
+                lines,
+                ..Limits::default()
+            })
+        }
+
+        fn current(find: &Find) -> Option<(usize, usize, Range<usize>)> {
+            find.current()
+                .map(|(pane, found)| (pane, found.line, found.range.clone()))
+        }
+
+        #[test]
+        fn matches_the_displayed_text() {
+            let mut stdout = buffer(10);
+            stdout.push(b"\x1b[31mfoo\x1b[0m bar foo\n".to_vec());
+
+            let mut find = find("foo");
+            find.search(&[&stdout], Encoding::Utf8);
+
+            assert_eq!(find.count(), 2);
+            assert_eq!(current(&find), Some((0, 0, 0..3)));
+            assert_eq!(find.highlights(0).line(0), [(0..3, true), (8..11, false)]);
+        }
+
+        #[test]
+        fn steps_through_both_panes_and_wraps_around() {
+            let (mut stdout, mut stderr) = (buffer(10), buffer(10));
+            stdout.push(b"x\n".to_vec());
+            stderr.push(b"x\n".to_vec());
+
+            let mut find = find("x");
+            find.search(&[&stdout, &stderr], Encoding::Utf8);
+
+            find.update(Message::Next);
+            assert_eq!(current(&find), Some((1, 0, 0..1)));
+            find.update(Message::Next);
+            assert_eq!(current(&find), Some((0, 0, 0..1)));
+            find.update(Message::Previous);
+            assert_eq!(current(&find), Some((1, 0, 0..1)));
+        }
+
+        #[test]
+        fn keeps_the_current_match_as_lines_are_truncated() {
+            let mut output = buffer(2);
+            let mut find = find("a");
+            find.search(&[&output], Encoding::Utf8);
+
+            let mut push = |find: &mut Find, line: &[u8]| {
+                output.push(line.to_vec());
+                find.scan(0, &output, Encoding::Utf8);
+            };
+
+            push(&mut find, b"a\n");
+            push(&mut find, b"a\n");
+            find.update(Message::Next);
+            find.update(Message::Next);
+            assert_eq!(current(&find), Some((0, 1, 0..1)));
+
+            // Drops the first line & its match, the current one stays put
+            push(&mut find, b"b\n");
+            assert_eq!(find.count(), 1);
+            assert_eq!(current(&find), Some((0, 1, 0..1)));
+
+            push(&mut find, b"c\n");
+            assert_eq!(find.count(), 0);
+            assert_eq!(current(&find), None);
+        }
+
+        #[test]
+        fn filters_lines_with_a_match() {
+            let mut output = buffer(10);
+            for line in ["a a\n", "b\n", "a\n"] {
+                output.push(line.as_bytes().to_vec());
+            }
+
+            let mut find = find("a");
+            find.update(Message::Filter(true));
+            find.search(&[&output], Encoding::Utf8);
+
+            assert_eq!(find.highlights(0).lines, Some(vec![0, 2]));
+        }
+
+        #[test]
+        fn reports_invalid_patterns() {
+            let mut find = find("(");
+            assert!(find.error.is_none());
+
+            find.update(Message::Regex(true));
+            assert!(find.pattern.is_none());
+            assert!(find.error.is_some());
+        }
+    }
+}
+
+mod output {
+    use std::borrow::Cow;
+    use std::ops::Range;
+
+    use iced::widget::scrollable::RelativeOffset;
+    use iced::widget::{scrollable, Scrollable};
+    use iced::{Color, Length};
+    use process_backend::buffer::{Buffer, Encoding, Line};
+
+    use crate::find::Highlights;
+
+    const BYTES_PER_ROW: usize = 16;
+
+    /// Scrollable view over a buffer that only lays out & draws the lines
+    /// within the viewport
+    pub fn view<'a, T: Line + 'a, Message: 'a>(
+        buffer: &'a Buffer<T>,
+        encoding: Encoding,
+        color: impl Fn(&T) -> Color + 'a,
+        highlights: Highlights<'a>,
+    ) -> Scrollable<'a, Message> {
+        let text = Text {
+            buffer,
+            encoding,
+            color,
+            highlights,
+        };
+
+        scrollable(widget::Lines::new(text)).height(Length::Fill)
+    }
+
+    /// A line tagged with when it was pushed, to interleave buffers by
+    #[derive(Debug)]
+    pub struct Numbered<T> {
+        pub seq: usize,
+        pub line: T,
+    }
+
+    impl<T: Line> Line for Numbered<T> {
+        fn size(&self) -> usize {
+            self.line.size()
+        }
+
+        fn text(&self, encoding: Encoding) -> Cow<'_, str> {
+            self.line.text(encoding)
+        }
+    }
+
+    /// A buffer shown interleaved with others, and the highlights of its
+    /// find pane if it's searched
+    pub struct Source<'a, T> {
+        pub buffer: &'a Buffer<Numbered<T>>,
+        pub highlights: Option<Highlights<'a>>,
+    }
+
+    /// Lines of several buffers in the order they were pushed, e.g. the
+    /// stdout, stderr & stdin of a running job, each capped on its own
+    pub struct Interleaved<'a, T> {
+        sources: Vec<Source<'a, T>>,
+        /// Sources that aren't searched are hidden while the others are
+        /// filtered
+        filtered: bool,
+    }
+
+    impl<'a, T: Line> Interleaved<'a, T> {
+        pub fn new(sources: Vec<Source<'a, T>>) -> Self {
+            let filtered = sources.iter().any(|source| {
+                matches!(&source.highlights, Some(Highlights { lines: Some(_), .. }))
+            });
+
+            Self { sources, filtered }
+        }
+
+        pub fn rows(&self) -> usize {
+            (0..self.sources.len())
+                .map(|source| self.shown(source))
+                .sum()
+        }
+
+        /// Lines dropped from the start of every source
+        pub fn truncated(&self) -> usize {
+            self.sources
+                .iter()
+                .map(|source| source.buffer.truncated())
+                .sum()
+        }
+
+        /// Row a line of a source is shown in, counting the line from the
+        /// first ever pushed
+        pub fn row(&self, source: usize, line: usize) -> Option<usize> {
+            let index = partition(self.shown(source), |index| {
+                self.line(source, index).is_some_and(|shown| shown < line)
+            });
+            (self.line(source, index)? == line).then_some(())?;
+
+            Some(index + self.before(source, self.seq(source, index)))
+        }
+
+        pub fn view<Message: 'a>(
+            self,
+            encoding: Encoding,
+            color: impl Fn(&T) -> Color + 'a,
+        ) -> Scrollable<'a, Message> {
+            let merged = Merged {
+                interleaved: self,
+                encoding,
+                color,
+            };
+
+            scrollable(widget::Lines::new(merged)).height(Length::Fill)
+        }
+
+        /// Lines of a source that are shown
+        fn shown(&self, source: usize) -> usize {
+            let source = &self.sources[source];
+
+            match &source.highlights {
+                Some(Highlights {
+                    lines: Some(lines), ..
+                }) => lines.len(),
+                _ if self.filtered => 0,
+                _ => source.buffer.len(),
+            }
+        }
+
+        /// Line shown at an index of a source's rows
+        fn line(&self, source: usize, index: usize) -> Option<usize> {
+            let source = &self.sources[source];
+
+            match &source.highlights {
+                Some(Highlights {
+                    lines: Some(lines), ..
+                }) => lines.get(index).copied(),
+                _ if self.filtered => None,
+                _ => (index < source.buffer.len()).then_some(source.buffer.truncated() + index),
+            }
+        }
+
+        fn get(&self, source: usize, line: usize) -> Option<&'a Numbered<T>> {
+            let buffer = self.sources[source].buffer;
+
+            buffer.get(line.checked_sub(buffer.truncated())?)
+        }
+
+        fn seq(&self, source: usize, index: usize) -> usize {
+            self.line(source, index)
+                .and_then(|line| self.get(source, line))
+                .map_or(usize::MAX, |line| line.seq)
+        }
+
+        /// Rows of the other sources pushed before `seq`
+        fn before(&self, source: usize, seq: usize) -> usize {
+            (0..self.sources.len())
+                .filter(|other| *other != source)
+                .map(|other| partition(self.shown(other), |index| self.seq(other, index) < seq))
+                .sum()
+        }
+
+        /// Source & line shown in a row
+        fn at(&self, row: usize) -> Option<(usize, usize)> {
+            (0..self.sources.len()).find_map(|source| {
+                let row_of = |index| index + self.before(source, self.seq(source, index));
+                let index = partition(self.shown(source), |index| row_of(index) < row);
+
+                (index < self.shown(source) && row_of(index) == row)
+                    .then(|| self.line(source, index).map(|line| (source, line)))
+                    .flatten()
+            })
+        }
+    }
+
+    /// First index in `0..len` the predicate is false for, given it holds
+    /// for a prefix
+    fn partition(len: usize, predicate: impl Fn(usize) -> bool) -> usize {
+        let (mut low, mut high) = (0, len);
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            if predicate(middle) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        low
+    }
+
+    /// Scroll offset that brings a row fully into view, given the viewport
+    /// is at least a row tall
+    pub fn offset(row: usize, rows: usize, truncated: usize) -> RelativeOffset {
+        // The truncation marker takes the first row
+        let marker = usize::from(truncated > 0);
+        let last = (rows + marker).saturating_sub(1).max(1);
+
+        RelativeOffset {
+            x: 0.0,
+            y: ((row + marker) as f32 / last as f32).min(1.0),
+        }
+    }
+
+    /// Hex dump of the raw output, `xxd` style
+    pub fn hex<'a, Message: 'a>(
+        buffer: &'a Buffer,
+        offsets: &'a Offsets,
+        color: Color,
+    ) -> Scrollable<'a, Message> {
+        let hex = Hex {
+            buffer,
+            offsets,
+            color,
+        };
+
+        scrollable(widget::Lines::new(hex)).height(Length::Fill)
+    }
+
+    /// Where each line of a finished buffer starts in the raw output
+    #[derive(Debug, Default)]
+    pub struct Offsets {
+        starts: Vec<usize>,
+        size: usize,
+    }
+
+    impl Offsets {
+        pub fn new(buffer: &Buffer) -> Self {
+            let mut starts = Vec::with_capacity(buffer.len());
+            let mut size = 0;
+
+            for index in 0..buffer.len() {
+                starts.push(size);
+                size += buffer.get(index).map_or(0, Vec::len);
+            }
+
+            Self { starts, size }
+        }
+    }
+
+    /// What the widget draws, one row at a time
+    pub trait Rows {
+        fn len(&self) -> usize;
+
+        /// Lines dropped from the start of the output
+        fn truncated(&self) -> usize;
+
+        /// Text of a row, may contain ANSI escapes, and its default color
+        fn row(&self, index: usize) -> Option<(Cow<'_, str>, Color)>;
+
+        /// Draw every character in a fixed width cell, so columns line up
+        fn monospace(&self) -> bool {
+            false
+        }
+
+        /// Ranges of the displayed text to highlight, and whether each is
+        /// the current match
+        fn highlights(&self, _index: usize) -> Vec<(Range<usize>, bool)> {
+            Vec::new()
+        }
+    }
+
+    struct Text<'a, T, F> {
+        buffer: &'a Buffer<T>,
+        encoding: Encoding,
+        color: F,
+        highlights: Highlights<'a>,
+    }
+
+    impl<'a, T, F> Text<'a, T, F> {
+        /// Line shown in a row, counted from the first line ever pushed
+        fn line(&self, index: usize) -> Option<usize> {
+            match &self.highlights.lines {
+                Some(lines) => lines.get(index).copied(),
+                None => Some(self.buffer.truncated() + index),
+            }
+        }
+    }
+
+    impl<'a, T: Line, F: Fn(&T) -> Color> Rows for Text<'a, T, F> {
+        fn len(&self) -> usize {
+            match &self.highlights.lines {
+                Some(lines) => lines.len(),
+                None => self.buffer.len(),
+            }
+        }
+
+        fn truncated(&self) -> usize {
+            self.buffer.truncated()
+        }
+
+        fn row(&self, index: usize) -> Option<(Cow<'_, str>, Color)> {
+            let line = self.line(index)?.checked_sub(self.buffer.truncated())?;
+            let line = self.buffer.get(line)?;
+
+            Some((line.text(self.encoding), (self.color)(line)))
+        }
+
+        fn highlights(&self, index: usize) -> Vec<(Range<usize>, bool)> {
+            self.line(index)
+                .map(|line| self.highlights.line(line))
+                .unwrap_or_default()
+        }
+    }
+
+    struct Merged<'a, T, F> {
+        interleaved: Interleaved<'a, T>,
+        encoding: Encoding,
+        color: F,
+    }
+
+    impl<'a, T: Line, F: Fn(&T) -> Color> Rows for Merged<'a, T, F> {
+        fn len(&self) -> usize {
+            self.interleaved.rows()
+        }
+
+        fn truncated(&self) -> usize {
+            self.interleaved.truncated()
+        }
+
+        fn row(&self, index: usize) -> Option<(Cow<'_, str>, Color)> {
+            let (source, line) = self.interleaved.at(index)?;
+            let line = &self.interleaved.get(source, line)?.line;
+
+            Some((line.text(self.encoding), (self.color)(line)))
+        }
+
+        fn highlights(&self, index: usize) -> Vec<(Range<usize>, bool)> {
+            self.interleaved
+                .at(index)
+                .and_then(|(source, line)| {
+                    let highlights = self.interleaved.sources[source].highlights.as_ref()?;
+                    Some(highlights.line(line))
+                })
+                .unwrap_or_default()
+        }
+    }
+
+    struct Hex<'a> {
+        buffer: &'a Buffer,
+        offsets: &'a Offsets,
+        color: Color,
+    }
+
+    impl<'a> Hex<'a> {
+        /// The bytes of a row, which may span several lines
+        fn bytes(&self, offset: usize) -> Vec<u8> {
+            let mut index = self
+                .offsets
+                .starts
+                .partition_point(|start| *start <= offset)
+                - 1;
+            let mut skip = offset - self.offsets.starts[index];
+            let mut bytes = Vec::with_capacity(BYTES_PER_ROW);
+
+            while let Some(line) = self.buffer.get(index) {
+                let wanted = BYTES_PER_ROW - bytes.len();
+                bytes.extend(line.iter().skip(skip).take(wanted));
+
+                if bytes.len() == BYTES_PER_ROW {
+                    break;
+                }
+                skip = 0;
+                index += 1;
+            }
+
+            bytes
+        }
+    }
+
+    impl<'a> Rows for Hex<'a> {
+        fn len(&self) -> usize {
+            (self.offsets.size + BYTES_PER_ROW - 1) / BYTES_PER_ROW
+        }
+
+        fn truncated(&self) -> usize {
+            self.buffer.truncated()
+        }
+
+        fn row(&self, index: usize) -> Option<(Cow<'_, str>, Color)> {
+            let offset = index * BYTES_PER_ROW;
+            if offset >= self.offsets.size {
+                return None;
+            }
+
+            let bytes = self.bytes(offset);
+            let hex = (0..BYTES_PER_ROW)
+                .map(|index| match bytes.get(index) {
+                    Some(byte) => format!("{byte:02x}"),
+                    None => "  ".into(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|byte| match byte {
+                    b' ' | b'!'..=b'~' => char::from(*byte),
+                    _ => '.',
+                })
+                .collect::<String>();
+
+            Some((format!("{offset:08x}  {hex}  {ascii}").into(), self.color))
+        }
+
+        fn monospace(&self) -> bool {
+            true
+        }
+    }
+
+    mod widget {
+        use iced::alignment;
+        use iced::{Color, Element, Font, Length, Point, Rectangle, Renderer, Size};
+        use iced_native::layout::{self, Layout};
+        use iced_native::renderer::{self, Renderer as _};
+        use iced_native::text::{self, Renderer as _};
+        use iced_native::widget::{Tree, Widget};
+
+        use super::Rows;
+        use crate::ansi;
+
+        const LINE_HEIGHT: f32 = 20.0;
+        const FONT_SIZE: u16 = 16;
+        /// Width of a character in monospace rows
+        const CELL_WIDTH: f32 = 10.0;
+        const TRUNCATED_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+        const MATCH_COLOR: Color = Color::from_rgba(1.0, 0.85, 0.2, 0.5);
+        const CURRENT_MATCH_COLOR: Color = Color::from_rgb(1.0, 0.6, 0.1);
+
+        pub struct Lines<R> {
+            rows: R,
+        }
+
+        impl<R: Rows> Lines<R> {
+            pub fn new(rows: R) -> Self {
+                Self { rows }
+            }
+
+            /// Rows to lay out, including the truncation marker
+            fn rows(&self) -> usize {
+                self.rows.len() + usize::from(self.rows.truncated() > 0)
+            }
+        }
+
+        impl<R: Rows, Message> Widget<Message, Renderer> for Lines<R> {
+            fn width(&self) -> Length {
+                Length::Fill
+            }
+
+            fn height(&self) -> Length {
+                Length::Shrink
+            }
+
+            fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+                let width = limits.width(Length::Fill).max().width;
+
+                layout::Node::new(Size::new(width, self.rows() as f32 * LINE_HEIGHT))
+            }
+
+            fn draw(
+                &self,
+                _tree: &Tree,
+                renderer: &mut Renderer,
+                _theme: &iced::Theme,
+                _style: &renderer::Style,
+                layout: Layout<'_>,
+                _cursor_position: Point,
+                viewport: &Rectangle,
+            ) {
+                let bounds = layout.bounds();
+
+                let first = ((viewport.y - bounds.y) / LINE_HEIGHT).floor().max(0.0) as usize;
+                let last = (((viewport.y + viewport.height - bounds.y) / LINE_HEIGHT).ceil()
+                    as usize)
+                    .min(self.rows());
+
+                for row in first..last {
+                    let y = bounds.y + row as f32 * LINE_HEIGHT;
+
+                    let index = match self.rows.truncated() {
+                        0 => row,
+                        truncated if row == 0 => {
+                            fill_text(
+                                renderer,
+                                &format!("… {truncated} earlier lines truncated"),
+                                Point::new(bounds.x, y),
+                                TRUNCATED_COLOR,
+                            );
+                            continue;
+                        }
+                        _ => row - 1,
+                    };
+
+                    let Some((line, foreground)) = self.rows.row(index) else {
+                        break;
+                    };
+
+                    if self.rows.monospace() {
+                        for (column, c) in line.chars().enumerate() {
+                            let x = bounds.x + column as f32 * CELL_WIDTH;
+
+                            // The rest of the line is clipped anyway
+                            if x > viewport.x + viewport.width {
+                                break;
+                            }
+                            if c == ' ' {
+                                continue;
+                            }
+
+                            fill_text(
+                                renderer,
+                                c.encode_utf8(&mut [0; 4]),
+                                Point::new(x, y),
+                                foreground,
+                            );
+                        }
+                        continue;
+                    }
+
+                    let highlights = self.rows.highlights(index);
+
+                    let mut x = bounds.x;
+                    // Into the displayed text, which is what matches index into
+                    let mut offset = 0;
+                    for span in ansi::parse(&line) {
+                        let (foreground, background) = span.style.colors(foreground, Color::WHITE);
+                        let width = renderer.measure_width(&span.text, FONT_SIZE, Font::Default);
+                        let span_bounds =
+                            Rectangle::new(Point::new(x, y), Size::new(width, LINE_HEIGHT));
+
+                        if let Some(background) = background {
+                            fill_quad(renderer, span_bounds, background);
+                        }
+
+                        for (range, current) in &highlights {
+                            let start = range.start.clamp(offset, offset + span.text.len());
+                            let end = range.end.clamp(offset, offset + span.text.len());
+                            if start == end {
+                                continue;
+                            }
+
+                            let before = renderer.measure_width(
+                                &span.text[..start - offset],
+                                FONT_SIZE,
+                                Font::Default,
+                            );
+                            let width = renderer.measure_width(
+                                &span.text[start - offset..end - offset],
+                                FONT_SIZE,
+                                Font::Default,
+                            );
+
+                            fill_quad(
+                                renderer,
+                                Rectangle::new(
+                                    Point::new(x + before, y),
+                                    Size::new(width, LINE_HEIGHT),
+                                ),
+                                if *current {
+                                    CURRENT_MATCH_COLOR
+                                } else {
+                                    MATCH_COLOR
+                                },
+                            );
+                        }
+                        offset += span.text.len();
+
+                        fill_text(renderer, &span.text, Point::new(x, y), foreground);
+
+                        if span.style.underline {
+                            fill_quad(
+                                renderer,
+                                Rectangle {
+                                    y: y + LINE_HEIGHT - 2.0,
+                                    height: 1.0,
+                                    ..span_bounds
+                                },
+                                foreground,
+                            );
+                        }
+
+                        x += width;
+
+                        // The rest of the line is clipped anyway
+                        if x > viewport.x + viewport.width {
+                            break;
+                        }
+                    }
                 }
-                State::Running {
-                    mut receiver,
-                    mut processes,
-                } => loop {
-                    let input = {
-                        let processes =
-                            FuturesUnordered::from_iter(processes.iter_mut().map(|(id, child)| {
-                                child.wait().map(|result| Input::Process(*id, result))
-                            }));
-
-                        let receiver = receiver
-                            .recv()
-                            .into_stream()
-                            .filter_map(|event| async move { event.map(Input::Event) })
-                            .boxed();
-
-                        stream::select(processes, receiver)
-                            .next()
-                            .await
-                            .expect("Await input")
+            }
+        }
+
+        fn fill_text(renderer: &mut Renderer, content: &str, position: Point, color: Color) {
+            renderer.fill_text(text::Text {
+                content,
+                bounds: Rectangle::new(position, Size::new(f32::INFINITY, LINE_HEIGHT)),
+                size: f32::from(FONT_SIZE),
+                color,
+                font: Font::Default,
+                horizontal_alignment: alignment::Horizontal::Left,
+                vertical_alignment: alignment::Vertical::Top,
+            });
+        }
+
+        fn fill_quad(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds,
+                    border_radius: 0.0.into(),
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                },
+                color,
+            );
+        }
+
+        impl<'a, R: Rows + 'a, Message> From<Lines<R>> for Element<'a, Message> {
+            fn from(lines: Lines<R>) -> Self {
+                Element::new(lines)
+            }
+        }
+    }
+}
+
+mod terminal {
+    use std::collections::VecDeque;
+
+    use iced::widget::canvas::{self, event, Canvas, Cursor, Frame, Geometry};
+    use iced::{mouse, Color, Element, Length, Point, Rectangle, Size as Bounds, Theme};
+    use process_backend::Size;
+
+    use crate::ansi::Style;
+
+    const CELL_WIDTH: f32 = 8.4;
+    const CELL_HEIGHT: f32 = 18.0;
+    const FONT_SIZE: f32 = 14.0;
+    const SCROLLBACK: usize = 5_000;
+
+    const BACKGROUND: Color = Color::from_rgb(0.1, 0.1, 0.1);
+    const FOREGROUND: Color = Color::from_rgb(0.85, 0.85, 0.85);
+
+    /// Grid size that fits within `width` x `height` logical pixels
+    fn size_for(width: f32, height: f32) -> Size {
+        Size {
+            rows: (height / CELL_HEIGHT).max(1.0) as u16,
+            cols: (width / CELL_WIDTH).max(1.0) as u16,
+        }
+    }
+
+    pub struct Terminal {
+        parser: vte::Parser,
+        grid: Grid,
+    }
+
+    impl std::fmt::Debug for Terminal {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Terminal")
+                .field("size", &self.grid.size)
+                .finish()
+        }
+    }
+
+    impl Terminal {
+        pub fn new(size: Size) -> Self {
+            Self {
+                parser: vte::Parser::new(),
+                grid: Grid::new(size),
+            }
+        }
+
+        /// Feeds raw output through the emulator, returning any replies
+        /// (e.g. cursor position reports) that should be written back
+        pub fn advance(&mut self, bytes: &[u8]) -> Option<String> {
+            for byte in bytes {
+                self.parser.advance(&mut self.grid, *byte);
+            }
+
+            (!self.grid.replies.is_empty()).then(|| std::mem::take(&mut self.grid.replies))
+        }
+
+        pub fn resize(&mut self, size: Size) {
+            self.grid.resize(size);
+        }
+
+        /// Reports the grid size that fits the space it's laid out in
+        pub fn view(&self) -> Element<Size> {
+            Canvas::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .into()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct State {
+        /// Lines scrolled back into history, 0 follows the live screen
+        offset: usize,
+        /// Last size reported for the bounds
+        size: Option<Size>,
+    }
+
+    impl canvas::Program<Size> for Terminal {
+        type State = State;
+
+        fn update(
+            &self,
+            state: &mut State,
+            event: canvas::Event,
+            bounds: Rectangle,
+            cursor: Cursor,
+        ) -> (event::Status, Option<Size>) {
+            // Bounds are only known here, so a new layout is picked up with
+            // whatever event comes after it
+            let size = size_for(bounds.width, bounds.height);
+            let resized = (state.size != Some(size)).then(|| {
+                state.size = Some(size);
+                size
+            });
+
+            if let canvas::Event::Mouse(mouse::Event::WheelScrolled { delta }) = event {
+                if cursor.is_over(&bounds) {
+                    let lines = match delta {
+                        mouse::ScrollDelta::Lines { y, .. } => y * 3.0,
+                        mouse::ScrollDelta::Pixels { y, .. } => y / CELL_HEIGHT,
                     };
 
-                    match input {
-                        Input::Event(event) => match event {
-                            Event::Wait(id, child) => processes.push((id, child)),
-                            Event::Close => {
-                                if !processes.is_empty() {
-                                    future::join_all(
-                                        processes.iter_mut().map(|(_, child)| child.kill()),
-                                    )
-                                    .await;
-                                }
+                    state.offset = (state.offset as f32 + lines)
+                        .clamp(0.0, self.grid.scrollback.len() as f32)
+                        as usize;
+
+                    return (event::Status::Captured, resized);
+                }
+            }
+
+            (event::Status::Ignored, resized)
+        }
+
+        fn draw(
+            &self,
+            state: &State,
+            _theme: &Theme,
+            bounds: Rectangle,
+            _cursor: Cursor,
+        ) -> Vec<Geometry> {
+            let mut frame = Frame::new(bounds.size());
+            frame.fill_rectangle(Point::ORIGIN, bounds.size(), BACKGROUND);
+
+            let grid = &self.grid;
+            let offset = state.offset.min(grid.scrollback.len());
+            let history = grid.scrollback.len() - offset;
+
+            let lines = grid
+                .scrollback
+                .iter()
+                .skip(history)
+                .chain(grid.lines.iter())
+                .take(grid.size.rows as usize);
+
+            for (row, line) in lines.enumerate() {
+                let y = row as f32 * CELL_HEIGHT;
+
+                for (col, cell) in line.iter().enumerate() {
+                    let x = col as f32 * CELL_WIDTH;
+                    let (foreground, background) = cell.style.colors(FOREGROUND, BACKGROUND);
+
+                    if let Some(background) = background {
+                        frame.fill_rectangle(
+                            Point::new(x, y),
+                            Bounds::new(CELL_WIDTH, CELL_HEIGHT),
+                            background,
+                        );
+                    }
+
+                    if cell.style.underline {
+                        frame.fill_rectangle(
+                            Point::new(x, y + CELL_HEIGHT - 2.0),
+                            Bounds::new(CELL_WIDTH, 1.0),
+                            foreground,
+                        );
+                    }
+
+                    if cell.c == ' ' {
+                        continue;
+                    }
+
+                    let text = canvas::Text {
+                        content: cell.c.to_string(),
+                        position: Point::new(x, y),
+                        color: foreground,
+                        size: FONT_SIZE,
+                        ..canvas::Text::default()
+                    };
+
+                    // Fake bold by overdrawing with a slight offset
+                    if cell.style.bold {
+                        frame.fill_text(canvas::Text {
+                            position: Point::new(x + 0.5, y),
+                            ..text.clone()
+                        });
+                    }
+
+                    frame.fill_text(text);
+                }
+            }
+
+            if offset == 0 && grid.cursor_visible {
+                let (row, col) = grid.cursor;
+
+                frame.fill_rectangle(
+                    Point::new(col as f32 * CELL_WIDTH, row as f32 * CELL_HEIGHT),
+                    Bounds::new(CELL_WIDTH, CELL_HEIGHT),
+                    Color {
+                        a: 0.5,
+                        ..FOREGROUND
+                    },
+                );
+            }
+
+            vec![frame.into_geometry()]
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Cell {
+        c: char,
+        style: Style,
+    }
+
+    impl Cell {
+        fn blank(style: Style) -> Self {
+            // Erased cells keep the background but nothing else
+            Self {
+                c: ' ',
+                style: Style {
+                    background: style.background,
+                    ..Style::default()
+                },
+            }
+        }
+    }
+
+    type Line = Vec<Cell>;
+
+    struct Grid {
+        size: Size,
+        lines: Vec<Line>,
+        scrollback: VecDeque<Line>,
+        /// Primary screen lines while the alternate screen is active
+        primary: Option<Vec<Line>>,
+        cursor: (usize, usize),
+        saved_cursor: ((usize, usize), Style),
+        style: Style,
+        /// Set after printing in the last column, the next print wraps
+        wrap_pending: bool,
+        cursor_visible: bool,
+        scroll_top: usize,
+        scroll_bottom: usize,
+        replies: String,
+    }
+
+    impl Grid {
+        fn new(size: Size) -> Self {
+            let size = Size {
+                rows: size.rows.max(1),
+                cols: size.cols.max(1),
+            };
+
+            Self {
+                size,
+                lines: vec![blank_line(size.cols, Style::default()); size.rows as usize],
+                scrollback: VecDeque::new(),
+                primary: None,
+                cursor: (0, 0),
+                saved_cursor: ((0, 0), Style::default()),
+                style: Style::default(),
+                wrap_pending: false,
+                cursor_visible: true,
+                scroll_top: 0,
+                scroll_bottom: size.rows as usize - 1,
+                replies: String::new(),
+            }
+        }
+
+        fn rows(&self) -> usize {
+            self.size.rows as usize
+        }
+
+        fn cols(&self) -> usize {
+            self.size.cols as usize
+        }
+
+        fn resize(&mut self, size: Size) {
+            let size = Size {
+                rows: size.rows.max(1),
+                cols: size.cols.max(1),
+            };
+            if size == self.size {
+                return;
+            }
+
+            for line in self
+                .lines
+                .iter_mut()
+                .chain(self.primary.iter_mut().flatten())
+            {
+                line.resize(size.cols as usize, Cell::blank(Style::default()));
+            }
+
+            // Shrinking pushes lines above the cursor into the scrollback
+            while self.lines.len() > size.rows as usize {
+                if self.cursor.0 > 0 {
+                    let line = self.lines.remove(0);
+                    self.push_scrollback(line);
+                    self.cursor.0 -= 1;
+                } else {
+                    self.lines.pop();
+                }
+            }
+            while self.lines.len() < size.rows as usize {
+                self.lines.push(blank_line(size.cols, Style::default()));
+            }
+            if let Some(primary) = &mut self.primary {
+                primary.resize(size.rows as usize, blank_line(size.cols, Style::default()));
+            }
+
+            self.size = size;
+            self.scroll_top = 0;
+            self.scroll_bottom = self.rows() - 1;
+            self.cursor = (
+                self.cursor.0.min(self.rows() - 1),
+                self.cursor.1.min(self.cols() - 1),
+            );
+            self.wrap_pending = false;
+        }
+
+        fn push_scrollback(&mut self, line: Line) {
+            if self.primary.is_some() {
+                return;
+            }
+
+            self.scrollback.push_back(line);
+            if self.scrollback.len() > SCROLLBACK {
+                self.scrollback.pop_front();
+            }
+        }
+
+        fn scroll_up(&mut self, count: usize) {
+            if self.scroll_top == 0 {
+                for row in 0..count.min(self.scroll_bottom + 1) {
+                    self.push_scrollback(self.lines[row].clone());
+                }
+            }
+
+            self.delete_lines(self.scroll_top, count);
+        }
+
+        /// Removes `count` lines at `row`, pulling up the rest of the
+        /// scroll region and filling the bottom with blanks
+        fn delete_lines(&mut self, row: usize, count: usize) {
+            for _ in 0..count.min(self.scroll_bottom + 1 - row) {
+                self.lines.remove(row);
+                self.lines
+                    .insert(self.scroll_bottom, blank_line(self.size.cols, self.style));
+            }
+        }
+
+        /// Inserts `count` blank lines at `row`, pushing the rest of the
+        /// scroll region down
+        fn insert_lines(&mut self, row: usize, count: usize) {
+            for _ in 0..count.min(self.scroll_bottom + 1 - row) {
+                self.lines.remove(self.scroll_bottom);
+                self.lines
+                    .insert(row, blank_line(self.size.cols, self.style));
+            }
+        }
+
+        fn scroll_down(&mut self, count: usize) {
+            self.insert_lines(self.scroll_top, count);
+        }
+
+        fn linefeed(&mut self) {
+            if self.cursor.0 == self.scroll_bottom {
+                self.scroll_up(1);
+            } else if self.cursor.0 < self.rows() - 1 {
+                self.cursor.0 += 1;
+            }
+        }
+
+        fn reverse_index(&mut self) {
+            if self.cursor.0 == self.scroll_top {
+                self.scroll_down(1);
+            } else {
+                self.cursor.0 = self.cursor.0.saturating_sub(1);
+            }
+        }
+
+        fn goto(&mut self, row: usize, col: usize) {
+            self.cursor = (row.min(self.rows() - 1), col.min(self.cols() - 1));
+            self.wrap_pending = false;
+        }
+
+        fn erase(&mut self, row: usize, cols: std::ops::Range<usize>) {
+            let blank = Cell::blank(self.style);
+            let line = &mut self.lines[row];
+            let end = cols.end.min(line.len());
+
+            for cell in &mut line[cols.start.min(end)..end] {
+                *cell = blank;
+            }
+        }
+
+        fn set_alternate_screen(&mut self, enabled: bool) {
+            match (enabled, self.primary.is_some()) {
+                (true, false) => {
+                    let alternate = vec![blank_line(self.size.cols, Style::default()); self.rows()];
+                    self.primary = Some(std::mem::replace(&mut self.lines, alternate));
+                }
+                (false, true) => {
+                    self.lines = self.primary.take().unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn blank_line(cols: u16, style: Style) -> Line {
+        vec![Cell::blank(style); cols as usize]
+    }
+
+    impl vte::Perform for Grid {
+        fn print(&mut self, c: char) {
+            if self.wrap_pending {
+                self.cursor.1 = 0;
+                self.linefeed();
+                self.wrap_pending = false;
+            }
+
+            let (row, col) = self.cursor;
+            self.lines[row][col] = Cell {
+                c,
+                style: self.style,
+            };
+
+            if col + 1 == self.cols() {
+                self.wrap_pending = true;
+            } else {
+                self.cursor.1 += 1;
+            }
+        }
+
+        fn execute(&mut self, byte: u8) {
+            match byte {
+                // Backspace
+                0x08 => {
+                    self.cursor.1 = self.cursor.1.saturating_sub(1);
+                    self.wrap_pending = false;
+                }
+                // Horizontal tab
+                0x09 => {
+                    self.cursor.1 = ((self.cursor.1 / 8 + 1) * 8).min(self.cols() - 1);
+                }
+                // Line feed, vertical tab, form feed
+                0x0a..=0x0c => self.linefeed(),
+                // Carriage return
+                0x0d => {
+                    self.cursor.1 = 0;
+                    self.wrap_pending = false;
+                }
+                _ => {}
+            }
+        }
+
+        fn csi_dispatch(
+            &mut self,
+            params: &vte::Params,
+            intermediates: &[u8],
+            _ignore: bool,
+            action: char,
+        ) {
+            let params: Vec<&[u16]> = params.iter().collect();
+            // Missing or zero parameters default to 1 for movement sequences
+            let arg = |index: usize| {
+                params
+                    .get(index)
+                    .map(|param| param[0] as usize)
+                    .filter(|n| *n > 0)
+                    .unwrap_or(1)
+            };
+            let mode = params.first().map(|param| param[0]).unwrap_or(0);
+            let (row, col) = self.cursor;
+
+            if intermediates == b"?" {
+                let enabled = match action {
+                    'h' => true,
+                    'l' => false,
+                    _ => return,
+                };
 
-                                return (Some(Message::Closed), State::Closed);
+                for param in &params {
+                    match param[0] {
+                        25 => self.cursor_visible = enabled,
+                        47 | 1047 => self.set_alternate_screen(enabled),
+                        1049 => {
+                            if enabled {
+                                self.saved_cursor = (self.cursor, self.style);
+                                self.set_alternate_screen(true);
+                                let (cursor, _) = self.saved_cursor;
+                                self.goto(cursor.0, cursor.1);
+                            } else {
+                                self.set_alternate_screen(false);
+                                let ((row, col), style) = self.saved_cursor;
+                                self.goto(row, col);
+                                self.style = style;
                             }
-                        },
-                        Input::Process(id, result) => {
-                            if let Some(index) = processes.iter().position(|(i, _)| *i == id) {
-                                let (_, mut process) = processes.remove(index);
-
-                                let mut stdout = String::new();
-                                if let Some(io) = process.stdout.as_mut() {
-                                    let mut reader = BufReader::new(io);
-                                    let _ = reader.read_to_string(&mut stdout).await;
-                                }
+                        }
+                        _ => {}
+                    }
+                }
+                return;
+            }
 
-                                let mut stderr = String::new();
-                                if let Some(io) = process.stdout.as_mut() {
-                                    let mut reader = BufReader::new(io);
-                                    let _ = reader.read_to_string(&mut stderr).await;
-                                }
+            if !intermediates.is_empty() {
+                return;
+            }
 
-                                let exited = result.map(|status| Exited {
-                                    status,
-                                    stdout,
-                                    stderr,
-                                });
-
-                                return (
-                                    Some(Message::ProcessExited(id, exited)),
-                                    State::Running {
-                                        receiver,
-                                        processes,
-                                    },
-                                );
+            match action {
+                'A' => self.goto(row.saturating_sub(arg(0)), col),
+                'B' | 'e' => self.goto(row + arg(0), col),
+                'C' | 'a' => self.goto(row, col + arg(0)),
+                'D' => self.goto(row, col.saturating_sub(arg(0))),
+                'E' => self.goto(row + arg(0), 0),
+                'F' => self.goto(row.saturating_sub(arg(0)), 0),
+                'G' | '`' => self.goto(row, arg(0) - 1),
+                'H' | 'f' => self.goto(arg(0) - 1, arg(1) - 1),
+                'd' => self.goto(arg(0) - 1, col),
+                'J' => {
+                    let rows = self.rows();
+                    let cols = self.cols();
+
+                    match mode {
+                        0 => {
+                            self.erase(row, col..cols);
+                            for row in row + 1..rows {
+                                self.erase(row, 0..cols);
+                            }
+                        }
+                        1 => {
+                            for row in 0..row {
+                                self.erase(row, 0..cols);
                             }
+                            self.erase(row, 0..col + 1);
                         }
+                        2 | 3 => {
+                            for row in 0..rows {
+                                self.erase(row, 0..cols);
+                            }
+                            if mode == 3 {
+                                self.scrollback.clear();
+                            }
+                        }
+                        _ => {}
                     }
+                }
+                'K' => match mode {
+                    0 => self.erase(row, col..self.cols()),
+                    1 => self.erase(row, 0..col + 1),
+                    2 => self.erase(row, 0..self.cols()),
+                    _ => {}
                 },
-                State::Closed => {
-                    time::sleep(Duration::from_secs(1)).await;
+                'L' | 'M' if (self.scroll_top..=self.scroll_bottom).contains(&row) => {
+                    if action == 'L' {
+                        self.insert_lines(row, arg(0));
+                    } else {
+                        self.delete_lines(row, arg(0));
+                    }
+
+                    self.goto(row, 0);
+                }
+                '@' => {
+                    let blank = Cell::blank(self.style);
+                    let line = &mut self.lines[row];
+
+                    for _ in 0..arg(0).min(line.len() - col) {
+                        line.insert(col, blank);
+                        line.pop();
+                    }
+                }
+                'P' => {
+                    let blank = Cell::blank(self.style);
+                    let line = &mut self.lines[row];
+
+                    for _ in 0..arg(0).min(line.len() - col) {
+                        line.remove(col);
+                        line.push(blank);
+                    }
+                }
+                'X' => self.erase(row, col..col + arg(0)),
+                'S' => self.scroll_up(arg(0)),
+                'T' => self.scroll_down(arg(0)),
+                'm' => self.style.apply(&params),
+                'r' => {
+                    let top = arg(0) - 1;
+                    let bottom = params
+                        .get(1)
+                        .map(|param| param[0] as usize)
+                        .filter(|n| *n > 0)
+                        .unwrap_or(self.rows())
+                        - 1;
+
+                    if top < bottom && bottom < self.rows() {
+                        self.scroll_top = top;
+                        self.scroll_bottom = bottom;
+                        self.goto(0, 0);
+                    }
+                }
+                's' => self.saved_cursor = (self.cursor, self.style),
+                'u' => {
+                    let ((row, col), style) = self.saved_cursor;
+                    self.goto(row, col);
+                    self.style = style;
+                }
+                'n' if mode == 6 => {
+                    self.replies
+                        .push_str(&format!("\x1b[{};{}R", row + 1, col + 1));
+                }
+                'c' if mode == 0 => self.replies.push_str("\x1b[?1;2c"),
+                _ => {}
+            }
+        }
 
-                    (None, State::Closed)
+        fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+            if !intermediates.is_empty() {
+                return;
+            }
+
+            match byte {
+                b'7' => self.saved_cursor = (self.cursor, self.style),
+                b'8' => {
+                    let ((row, col), style) = self.saved_cursor;
+                    self.goto(row, col);
+                    self.style = style;
                 }
+                b'D' => self.linefeed(),
+                b'E' => {
+                    self.cursor.1 = 0;
+                    self.linefeed();
+                }
+                b'M' => self.reverse_index(),
+                b'c' => *self = Grid::new(self.size),
+                _ => {}
             }
-        })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn terminal(rows: u16, cols: u16, output: &str) -> Terminal {
+            let mut terminal = Terminal::new(Size { rows, cols });
+            terminal.advance(output.as_bytes());
+            terminal
+        }
+
+        fn text<'a>(lines: impl IntoIterator<Item = &'a Line>) -> Vec<String> {
+            lines
+                .into_iter()
+                .map(|line| line.iter().map(|cell| cell.c).collect::<String>())
+                .map(|line| line.trim_end().to_owned())
+                .collect()
+        }
+
+        #[test]
+        fn wraps_after_the_last_column() {
+            let wrapped = terminal(3, 4, "abcdef");
+            assert_eq!(text(&wrapped.grid.lines), ["abcd", "ef", ""]);
+
+            // A full line followed by a newline doesn't leave a blank one
+            let exact = terminal(3, 4, "abcd\r\nx");
+            assert_eq!(text(&exact.grid.lines), ["abcd", "x", ""]);
+        }
+
+        #[test]
+        fn scrolls_lines_off_the_top_into_the_scrollback() {
+            let terminal = terminal(2, 4, "a\r\nb\r\nc");
+
+            assert_eq!(text(&terminal.grid.lines), ["b", "c"]);
+            assert_eq!(text(&terminal.grid.scrollback), ["a"]);
+        }
+
+        #[test]
+        fn scrolls_within_the_scroll_region() {
+            let mut terminal = terminal(4, 4, "a\x1b[2;1Hb\x1b[3;1Hc\x1b[4;1Hd");
+
+            // Rows 2 to 3, then a newline at the bottom of the region
+            terminal.advance(b"\x1b[2;3r\x1b[3;1H\n");
+
+            assert_eq!(text(&terminal.grid.lines), ["a", "c", "", "d"]);
+            assert!(terminal.grid.scrollback.is_empty());
+        }
+
+        #[test]
+        fn restores_the_primary_screen_after_the_alternate_one() {
+            let mut terminal = terminal(2, 8, "main");
+
+            terminal.advance(b"\x1b[?1049h");
+            assert_eq!(text(&terminal.grid.lines), ["", ""]);
+
+            terminal.advance(b"alt\r\n\n\n");
+            terminal.advance(b"\x1b[?1049l");
+            assert_eq!(text(&terminal.grid.lines), ["main", ""]);
+            assert_eq!(terminal.grid.cursor, (0, 4));
+            // Nothing from the alternate screen is kept
+            assert!(terminal.grid.scrollback.is_empty());
+        }
+
+        #[test]
+        fn erases_lines_and_the_screen() {
+            let mut terminal = terminal(2, 5, "hello\r\nworld");
+
+            terminal.advance(b"\x1b[1;3H\x1b[K");
+            terminal.advance(b"\x1b[2;3H\x1b[1K");
+            assert_eq!(text(&terminal.grid.lines), ["he", "   ld"]);
+
+            terminal.advance(b"\x1b[2J");
+            assert_eq!(text(&terminal.grid.lines), ["", ""]);
+        }
     }
 }